@@ -0,0 +1,1080 @@
+// gltf-ibl-sampler-egui/src/astc.rs
+//
+// A simple ASTC HDR block encoder.
+//
+// Every block uses a single partition, color endpoint mode 11 (HDR RGB) with unquantized
+// endpoints, and a 4×4 grid of weights with 12 levels each. This is nowhere near as thorough as a
+// full search like `astcenc` performs, but it's fast and holds up well on the smooth gradients
+// that prefiltered environment maps mostly consist of. Blocks of a single color are written as
+// HDR void-extent blocks, which are lossless.
+
+use half::f16;
+
+// Weight grid of 4×4, 12 weight levels (trits and 2 bits), no dual plane.
+const BLOCK_MODE: u128 = 0x251;
+const COLOR_ENDPOINT_MODE_HDR_RGB: u128 = 11;
+const WEIGHT_GRID_SIZE: u32 = 4;
+const WEIGHT_COUNT: usize = (WEIGHT_GRID_SIZE * WEIGHT_GRID_SIZE) as usize;
+const COLOR_DATA_OFFSET: u32 = 17;
+
+// The low 64 bits of an HDR void-extent block with no extent coordinates.
+const VOID_EXTENT_HDR: u128 = 0xffff_ffff_ffff_fffc;
+
+// The largest endpoint value, in the 12-bit logarithmic space mode 11 works in, that still
+// decodes to a finite half float after rounding to the coarsest endpoint precision.
+const MAX_ENDPOINT: i32 = 0xf70;
+
+// The unquantized values of the 12 weight levels, indexed by their ISE value.
+static WEIGHT_UNQUANTIZED: [i32; 12] = [0, 64, 17, 47, 5, 59, 23, 41, 11, 53, 28, 36];
+
+// The number of bits that each of the fields of an endpoint mode 11 encoding has, indexed by
+// submode.
+static MODE_11_A_BITS: [u32; 8] = [9, 9, 10, 10, 11, 11, 12, 12];
+static MODE_11_B_BITS: [u32; 8] = [7, 8, 6, 7, 8, 6, 7, 6];
+static MODE_11_C_BITS: [u32; 8] = [6, 6, 7, 7, 6, 8, 7, 7];
+static MODE_11_D_BITS: [u32; 8] = [7, 6, 7, 6, 5, 6, 5, 6];
+
+struct Encoder {
+    // For each texel in a block, the weight grid points that contribute to it and by how much,
+    // in sixteenths.
+    infill: Vec<Vec<(usize, i32)>>,
+    // Maps five trits to their packed 8-bit representation.
+    trit_encodings: [u8; 243],
+}
+
+#[derive(Clone, Copy)]
+struct EncodedEndpoints {
+    values: [u8; 6],
+    // Decoded endpoints, as 16-bit logarithmic values.
+    decoded: [[i32; 3]; 2],
+}
+
+/// Compresses an RGBA floating-point image to ASTC HDR with the given block size.
+///
+/// Alpha is ignored; every texel decodes with an alpha of 1.
+pub(crate) fn encode_hdr(
+    pixels: &[f32],
+    width: u32,
+    height: u32,
+    block_width: u32,
+    block_height: u32,
+) -> Vec<u8> {
+    let encoder = Encoder::new(block_width, block_height);
+
+    let blocks_x = width.div_ceil(block_width);
+    let blocks_y = height.div_ceil(block_height);
+    let mut output = Vec::with_capacity((blocks_x * blocks_y) as usize * 16);
+
+    let mut texels = vec![[0.0; 3]; (block_width * block_height) as usize];
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            // Gather the block, clamping to the edge of the image.
+            for (texel_index, texel) in texels.iter_mut().enumerate() {
+                let x = (block_x * block_width + texel_index as u32 % block_width).min(width - 1);
+                let y = (block_y * block_height + texel_index as u32 / block_width).min(height - 1);
+                let pixel_index = (y * width + x) as usize * 4;
+                for channel in 0..3 {
+                    let value = pixels[pixel_index + channel];
+                    // This also flushes NaNs to zero.
+                    texel[channel] = if value > 0.0 {
+                        value.min(f16::MAX.to_f32())
+                    } else {
+                        0.0
+                    };
+                }
+            }
+
+            output.extend_from_slice(&encoder.encode_block(&texels).to_le_bytes());
+        }
+    }
+
+    output
+}
+
+impl Encoder {
+    fn new(block_width: u32, block_height: u32) -> Encoder {
+        let mut infill = vec![];
+        let scale_s = (1024 + block_width / 2) / (block_width - 1);
+        let scale_t = (1024 + block_height / 2) / (block_height - 1);
+        for t in 0..block_height {
+            for s in 0..block_width {
+                let gs = (scale_s * s * (WEIGHT_GRID_SIZE - 1) + 32) >> 6;
+                let gt = (scale_t * t * (WEIGHT_GRID_SIZE - 1) + 32) >> 6;
+                let (js, fs) = ((gs >> 4) as usize, (gs & 0xf) as i32);
+                let (jt, ft) = ((gt >> 4) as usize, (gt & 0xf) as i32);
+                let w11 = (fs * ft + 8) >> 4;
+                let grid_index = js + jt * WEIGHT_GRID_SIZE as usize;
+                let contributions = [
+                    (grid_index, 16 - fs - ft + w11),
+                    (grid_index + 1, fs - w11),
+                    (grid_index + WEIGHT_GRID_SIZE as usize, ft - w11),
+                    (grid_index + WEIGHT_GRID_SIZE as usize + 1, w11),
+                ];
+                infill.push(
+                    contributions
+                        .into_iter()
+                        .filter(|&(_, weight)| weight != 0)
+                        .collect(),
+                );
+            }
+        }
+
+        // Build the trit encoding table by inverting the decoding procedure. Picking the smallest
+        // encoding for each combination ensures that trailing zero trits encode to zero bits,
+        // which is needed when a trit block is truncated.
+        let mut trit_encodings = [0; 243];
+        let mut seen = [false; 243];
+        for packed in 0..=255 {
+            let trits = decode_trits(packed);
+            let index = trits
+                .iter()
+                .rev()
+                .fold(0, |index, &trit| index * 3 + trit as usize);
+            if !seen[index] {
+                seen[index] = true;
+                trit_encodings[index] = packed;
+            }
+        }
+
+        Encoder {
+            infill,
+            trit_encodings,
+        }
+    }
+
+    fn encode_block(&self, texels: &[[f32; 3]]) -> u128 {
+        if texels.iter().all(|texel| *texel == texels[0]) {
+            return encode_void_extent(texels[0]);
+        }
+
+        let lns_texels: Vec<[f32; 3]> =
+            texels.iter().map(|texel| texel.map(float_to_lns)).collect();
+
+        // Fit a line through the colors in logarithmic space and use its extent as the endpoints.
+        let (mean, axis) = principal_axis(&lns_texels);
+        let (mut t_min, mut t_max) = (f32::MAX, f32::MIN);
+        for texel in &lns_texels {
+            let t = dot(sub(*texel, mean), axis);
+            t_min = t_min.min(t);
+            t_max = t_max.max(t);
+        }
+        let target_endpoints = [t_min, t_max].map(|t| {
+            [0, 1, 2].map(|channel| {
+                ((mean[channel] + axis[channel] * t) / 16.0)
+                    .round()
+                    .clamp(0.0, MAX_ENDPOINT as f32) as i32
+            })
+        });
+        let endpoints = encode_endpoints(target_endpoints);
+
+        // Project each texel onto the decoded endpoints to find its ideal weight.
+        let decoded = endpoints
+            .decoded
+            .map(|endpoint| endpoint.map(|value| value as f32));
+        let direction = sub(decoded[1], decoded[0]);
+        let length_squared = dot(direction, direction);
+        let ideal_weights: Vec<f32> = lns_texels
+            .iter()
+            .map(|texel| {
+                if length_squared == 0.0 {
+                    0.0
+                } else {
+                    (dot(sub(*texel, decoded[0]), direction) / length_squared).clamp(0.0, 1.0)
+                        * 64.0
+                }
+            })
+            .collect();
+
+        // Distribute the ideal weights to the weight grid, and quantize.
+        let mut grid_weights = [0.0; WEIGHT_COUNT];
+        let mut grid_totals = [0.0; WEIGHT_COUNT];
+        for (texel_index, contributions) in self.infill.iter().enumerate() {
+            for &(grid_index, weight) in contributions {
+                grid_weights[grid_index] += ideal_weights[texel_index] * weight as f32;
+                grid_totals[grid_index] += weight as f32;
+            }
+        }
+        let mut weights = [0; WEIGHT_COUNT];
+        for (grid_index, weight) in weights.iter_mut().enumerate() {
+            let ideal = if grid_totals[grid_index] > 0.0 {
+                grid_weights[grid_index] / grid_totals[grid_index]
+            } else {
+                0.0
+            };
+            *weight = quantize_weight(ideal);
+        }
+
+        self.pack_block(&endpoints.values, &weights)
+    }
+
+    fn pack_block(&self, color_values: &[u8; 6], weights: &[u8; WEIGHT_COUNT]) -> u128 {
+        let mut block = BLOCK_MODE | COLOR_ENDPOINT_MODE_HDR_RGB << 13;
+        for (index, &value) in color_values.iter().enumerate() {
+            block |= (value as u128) << (COLOR_DATA_OFFSET + index as u32 * 8);
+        }
+
+        // Weights are stored as an integer sequence with trits, bit-reversed from the top of the
+        // block down.
+        let mut weight_bits = 0u128;
+        let mut weight_bit_count = 0;
+        let mut push_bits = |value: u32, count: u32| {
+            weight_bits |= ((value & ((1 << count) - 1)) as u128) << weight_bit_count;
+            weight_bit_count += count;
+        };
+        for chunk in weights.chunks(5) {
+            let mut trit_index = 0;
+            for &weight in chunk.iter().rev() {
+                trit_index = trit_index * 3 + (weight >> 2) as usize;
+            }
+            let packed = self.trit_encodings[trit_index] as u32;
+
+            // Trit bits are interleaved between the low bits of each value: 2, 2, 1, 2, 1.
+            for (index, &weight) in chunk.iter().enumerate() {
+                push_bits(weight as u32 & 3, 2);
+                match index {
+                    0 => push_bits(packed, 2),
+                    1 => push_bits(packed >> 2, 2),
+                    2 => push_bits(packed >> 4, 1),
+                    3 => push_bits(packed >> 5, 2),
+                    _ => push_bits(packed >> 7, 1),
+                }
+            }
+        }
+
+        block | weight_bits.reverse_bits()
+    }
+}
+
+fn encode_void_extent(color: [f32; 3]) -> u128 {
+    let mut block = VOID_EXTENT_HDR;
+    for (channel, value) in color.into_iter().chain([1.0]).enumerate() {
+        block |= (f16::from_f32(value).to_bits() as u128) << (64 + channel * 16);
+    }
+    block
+}
+
+// Converts a linear value to the piecewise-linear approximation of a logarithm that ASTC HDR
+// endpoints and interpolation use, scaled to 16 bits.
+fn float_to_lns(value: f32) -> f32 {
+    // Split the value into a half-float exponent and a mantissa in [0, 1).
+    let (exponent, mantissa) = if value < f32::powi(2.0, -14) {
+        (0, value * f32::powi(2.0, 14))
+    } else {
+        let bits = value.to_bits();
+        let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+        let mantissa = (bits & 0x7f_ffff) as f32 / (1 << 23) as f32;
+        (exponent, mantissa)
+    };
+
+    // Invert the mantissa transfer function that the decoder applies.
+    let transformed = mantissa * 8192.0;
+    let mantissa = if transformed < 1536.0 {
+        transformed / 3.0
+    } else if transformed < 5632.0 {
+        (transformed + 512.0) / 4.0
+    } else {
+        (transformed + 2048.0) / 5.0
+    };
+
+    exponent as f32 * 2048.0 + mantissa
+}
+
+fn principal_axis(points: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let mut mean = [0.0; 3];
+    for point in points {
+        mean = add(mean, *point);
+    }
+    mean = mean.map(|value| value / points.len() as f32);
+
+    let mut covariance = [[0.0; 3]; 3];
+    for point in points {
+        let offset = sub(*point, mean);
+        for (row, covariance_row) in covariance.iter_mut().enumerate() {
+            for (column, value) in covariance_row.iter_mut().enumerate() {
+                *value += offset[row] * offset[column];
+            }
+        }
+    }
+
+    // Power iteration, starting from the axis with the most variance, or the gray axis if the
+    // points are all the same.
+    let mut axis = (0..3)
+        .max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b]))
+        .map(|channel| covariance[channel])
+        .filter(|row| dot(*row, *row) > 0.0)
+        .unwrap_or([1.0, 1.0, 1.0]);
+    axis = normalize(axis);
+    for _ in 0..8 {
+        let next = covariance.map(|row| dot(row, axis));
+        if dot(next, next) == 0.0 {
+            break;
+        }
+        axis = normalize(next);
+    }
+    (mean, axis)
+}
+
+// Finds the best mode 11 encoding of the given pair of 12-bit endpoints.
+fn encode_endpoints(targets: [[i32; 3]; 2]) -> EncodedEndpoints {
+    let mut best: Option<(i64, EncodedEndpoints)> = None;
+    let mut consider = |values: [u8; 6], targets: [[i32; 3]; 2]| {
+        let decoded = decode_endpoints(&values);
+        if decoded
+            .iter()
+            .flatten()
+            .any(|&value| value > MAX_ENDPOINT << 4)
+        {
+            return;
+        }
+
+        let mut error = 0;
+        for (decoded_endpoint, target_endpoint) in decoded.iter().zip(targets.iter()) {
+            for (decoded, target) in decoded_endpoint.iter().zip(target_endpoint.iter()) {
+                error += ((decoded - (target << 4)) as i64).pow(2);
+            }
+        }
+        if !matches!(best, Some((best_error, _)) if best_error <= error) {
+            best = Some((error, EncodedEndpoints { values, decoded }));
+        }
+    };
+
+    // The direct submode can always represent the endpoints, if coarsely.
+    consider(encode_endpoints_direct(targets), targets);
+
+    // Try every other submode, with both endpoint orders. The second endpoint must hold the
+    // largest component, which becomes the major component.
+    for [low, high] in [targets, [targets[1], targets[0]]] {
+        let major_component = (0..3).max_by_key(|&channel| high[channel]).unwrap();
+        for mode in 0..8 {
+            consider(
+                encode_endpoints_mode_11(mode, major_component, low, high),
+                [low, high],
+            );
+        }
+    }
+
+    best.unwrap().1
+}
+
+fn encode_endpoints_direct(targets: [[i32; 3]; 2]) -> [u8; 6] {
+    let [low, high] = targets;
+    let red_green = |value: i32| ((value as f32 / 16.0).round() as i32).clamp(0, 0xf7) as u8;
+    let blue = |value: i32| 0x80 | ((value as f32 / 32.0).round() as i32).clamp(0, 0x7b) as u8;
+    [
+        red_green(low[0]),
+        red_green(high[0]),
+        red_green(low[1]),
+        red_green(high[1]),
+        blue(low[2]),
+        blue(high[2]),
+    ]
+}
+
+fn encode_endpoints_mode_11(
+    mode: usize,
+    major_component: usize,
+    low: [i32; 3],
+    high: [i32; 3],
+) -> [u8; 6] {
+    // Swizzle so that the major component comes first.
+    let swizzle = |color: [i32; 3]| match major_component {
+        1 => [color[1], color[0], color[2]],
+        2 => [color[2], color[1], color[0]],
+        _ => color,
+    };
+    let (low, high) = (swizzle(low), swizzle(high));
+
+    let shift = (mode as u32 >> 1) ^ 3;
+    let quantize = |value: i32, min: i32, max: i32| {
+        ((value as f32 / (1 << shift) as f32).round() as i32).clamp(min, max)
+    };
+    let unsigned_max = |bits: u32| (1 << bits) - 1;
+    let (b_bits, c_bits, d_bits) = (
+        MODE_11_B_BITS[mode],
+        MODE_11_C_BITS[mode],
+        MODE_11_D_BITS[mode],
+    );
+
+    // Quantize each field relative to the already-quantized fields it depends on, so that errors
+    // don't accumulate.
+    let a = quantize(high[0], 0, unsigned_max(MODE_11_A_BITS[mode]));
+    let b0 = quantize((a << shift) - high[1], 0, unsigned_max(b_bits));
+    let b1 = quantize((a << shift) - high[2], 0, unsigned_max(b_bits));
+    let c = quantize((a << shift) - low[0], 0, unsigned_max(c_bits));
+    let d_range = (-(1 << (d_bits - 1)), (1 << (d_bits - 1)) - 1);
+    let d0 = quantize(((a - b0 - c) << shift) - low[1], d_range.0, d_range.1);
+    let d1 = quantize(((a - b1 - c) << shift) - low[2], d_range.0, d_range.1);
+
+    // Place the bits whose location depends on the submode.
+    let bit = |value: i32, index: u32| ((value >> index) & 1) as u8;
+    let bit0 = match mode {
+        2 | 5 | 7 => bit(a, 9),
+        _ => bit(b0, 6),
+    };
+    let bit1 = match mode {
+        5 | 7 => bit(a, 10),
+        2 => bit(c, 6),
+        _ => bit(b1, 6),
+    };
+    let bit2 = match mode {
+        3 => bit(a, 9),
+        6 | 7 => bit(a, 11),
+        5 => bit(c, 7),
+        1 | 4 => bit(b0, 7),
+        _ => bit(d0, 6),
+    };
+    let bit3 = match mode {
+        3 | 5 | 6 | 7 => bit(c, 6),
+        1 | 4 => bit(b1, 7),
+        _ => bit(d1, 6),
+    };
+    let (bit4, bit5) = match mode {
+        4 | 6 => (bit(a, 9), bit(a, 10)),
+        _ => (bit(d0, 5), bit(d1, 5)),
+    };
+
+    let mode = mode as u8;
+    let major_component = major_component as u8;
+    [
+        a as u8,
+        (c & 0x3f) as u8 | bit(a, 8) << 6 | (mode & 1) << 7,
+        (b0 & 0x3f) as u8 | bit0 << 6 | (mode >> 1 & 1) << 7,
+        (b1 & 0x3f) as u8 | bit1 << 6 | (mode >> 2) << 7,
+        (d0 & 0x1f) as u8 | bit4 << 5 | bit2 << 6 | (major_component & 1) << 7,
+        (d1 & 0x1f) as u8 | bit5 << 5 | bit3 << 6 | (major_component >> 1) << 7,
+    ]
+}
+
+// Decodes mode 11 endpoints to 16-bit logarithmic values, exactly as a GPU would.
+fn decode_endpoints(values: &[u8; 6]) -> [[i32; 3]; 2] {
+    let v = values.map(|value| value as i32);
+    let major_component = (v[4] >> 7) | (v[5] >> 7) << 1;
+    if major_component == 3 {
+        return [
+            [v[0] << 8, v[2] << 8, (v[4] & 0x7f) << 9],
+            [v[1] << 8, v[3] << 8, (v[5] & 0x7f) << 9],
+        ];
+    }
+
+    let mode = ((v[1] >> 7) | (v[2] >> 7) << 1 | (v[3] >> 7) << 2) as usize;
+    let mut a = v[0] | (v[1] & 0x40) << 2;
+    let mut b0 = v[2] & 0x3f;
+    let mut b1 = v[3] & 0x3f;
+    let mut c = v[1] & 0x3f;
+    let mut d0 = v[4] & 0x1f;
+    let mut d1 = v[5] & 0x1f;
+
+    let bit0 = (v[2] >> 6) & 1;
+    let bit1 = (v[3] >> 6) & 1;
+    let bit2 = (v[4] >> 6) & 1;
+    let bit3 = (v[5] >> 6) & 1;
+    let bit4 = (v[4] >> 5) & 1;
+    let bit5 = (v[5] >> 5) & 1;
+
+    match mode {
+        2 | 5 | 7 => a |= bit0 << 9,
+        _ => b0 |= bit0 << 6,
+    }
+    match mode {
+        5 | 7 => a |= bit1 << 10,
+        2 => c |= bit1 << 6,
+        _ => b1 |= bit1 << 6,
+    }
+    match mode {
+        3 => a |= bit2 << 9,
+        6 | 7 => a |= bit2 << 11,
+        5 => c |= bit2 << 7,
+        1 | 4 => b0 |= bit2 << 7,
+        _ => d0 |= bit2 << 6,
+    }
+    match mode {
+        3 | 5 | 6 | 7 => c |= bit3 << 6,
+        1 | 4 => b1 |= bit3 << 7,
+        _ => d1 |= bit3 << 6,
+    }
+    match mode {
+        4 | 6 => a |= bit4 << 9 | bit5 << 10,
+        _ => {
+            d0 |= bit4 << 5;
+            d1 |= bit5 << 5;
+        }
+    }
+
+    // Sign-extend the `d` fields.
+    let d_shift = 32 - MODE_11_D_BITS[mode];
+    d0 = (d0 << d_shift) >> d_shift;
+    d1 = (d1 << d_shift) >> d_shift;
+
+    let shift = (mode as u32 >> 1) ^ 3;
+    let (a, b0, b1, c, d0, d1) = (
+        a << shift,
+        b0 << shift,
+        b1 << shift,
+        c << shift,
+        d0 << shift,
+        d1 << shift,
+    );
+
+    let mut low = [a - c, a - b0 - c - d0, a - b1 - c - d1];
+    let mut high = [a, a - b0, a - b1];
+    for endpoint in [&mut low, &mut high] {
+        *endpoint = endpoint.map(|value| value.clamp(0, 0xfff) << 4);
+        match major_component {
+            1 => endpoint.swap(0, 1),
+            2 => endpoint.swap(0, 2),
+            _ => {}
+        }
+    }
+    [low, high]
+}
+
+// Unpacks five trits from their 8-bit representation.
+fn decode_trits(packed: u8) -> [u8; 5] {
+    let bits = |high: u32, low: u32| (packed >> low) & ((1 << (high - low + 1)) - 1);
+
+    let (c, t3, t4);
+    if bits(4, 2) == 0b111 {
+        c = bits(7, 5) << 2 | bits(1, 0);
+        t4 = 2;
+        t3 = 2;
+    } else {
+        c = bits(4, 0);
+        if bits(6, 5) == 0b11 {
+            t4 = 2;
+            t3 = bits(7, 7);
+        } else {
+            t4 = bits(7, 7);
+            t3 = bits(6, 5);
+        }
+    }
+
+    let (t0, t1, t2);
+    if c & 0b11 == 0b11 {
+        t2 = 2;
+        t1 = c >> 4;
+        t0 = (c >> 3 & 1) << 1 | (c >> 2 & 1 & !(c >> 3) & 1);
+    } else if c >> 2 & 0b11 == 0b11 {
+        t2 = 2;
+        t1 = 2;
+        t0 = c & 0b11;
+    } else {
+        t2 = c >> 4;
+        t1 = c >> 2 & 0b11;
+        t0 = (c >> 1 & 1) << 1 | (c & 1 & !(c >> 1) & 1);
+    }
+
+    [t0, t1, t2, t3, t4]
+}
+
+fn quantize_weight(weight: f32) -> u8 {
+    (0..12)
+        .min_by(|&a, &b| {
+            let error_a = (WEIGHT_UNQUANTIZED[a as usize] as f32 - weight).abs();
+            let error_b = (WEIGHT_UNQUANTIZED[b as usize] as f32 - weight).abs();
+            error_a.total_cmp(&error_b)
+        })
+        .unwrap()
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = dot(a, a).sqrt();
+    a.map(|value| value / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A decoder for the blocks `encode_hdr` writes, written from the ASTC specification without
+    // any of the encoder's tables or helpers, so that the two can't share a mistake.
+    mod reference {
+        use half::f16;
+
+        // Reads `count` bits of `block`, starting at bit `offset`.
+        fn bits(block: u128, offset: u32, count: u32) -> u32 {
+            (block >> offset) as u32 & ((1 << count) - 1)
+        }
+
+        pub(super) struct WeightGrid {
+            pub(super) width: u32,
+            pub(super) height: u32,
+            // Whether each weight has a trit on top of its `bit_count` low bits.
+            pub(super) trits: bool,
+            pub(super) bit_count: u32,
+        }
+
+        // Decodes a block mode whose two lowest bits aren't both zero.
+        pub(super) fn decode_block_mode(block: u128) -> WeightGrid {
+            assert_ne!(bits(block, 0, 2), 0, "Unsupported block mode layout");
+            assert_eq!(bits(block, 10, 1), 0, "Expected a single weight plane");
+            let (a, b) = (bits(block, 5, 2), bits(block, 7, 2));
+            let (width, height) = match bits(block, 2, 2) {
+                0 => (b + 4, a + 2),
+                1 => (b + 8, a + 2),
+                2 => (a + 2, b + 8),
+                _ if bits(block, 8, 1) == 0 => (a + 2, (b & 1) + 6),
+                _ => ((b & 1) + 2, a + 2),
+            };
+            let range = bits(block, 4, 1) | bits(block, 0, 2) << 1;
+            let (trits, bit_count) = match (bits(block, 9, 1), range) {
+                (0, 2) => (false, 1),
+                (0, 3) => (true, 0),
+                (0, 4) => (false, 2),
+                (0, 6) => (true, 1),
+                (0, 7) => (false, 3),
+                (1, 3) => (true, 2),
+                (1, 4) => (false, 4),
+                (1, 6) => (true, 3),
+                (1, 7) => (false, 5),
+                _ => unimplemented!("Weight ranges with quints aren't supported"),
+            };
+            WeightGrid {
+                width,
+                height,
+                trits,
+                bit_count,
+            }
+        }
+
+        // The trit decoding procedure of the specification.
+        pub(super) fn decode_trits(t: u32) -> [u32; 5] {
+            let bit = |value: u32, index: u32| value >> index & 1;
+            let (c, t4, t3) = if t >> 2 & 0x7 == 0x7 {
+                ((t >> 5 & 0x7) << 2 | (t & 0x3), 2, 2)
+            } else if t >> 5 & 0x3 == 0x3 {
+                (t & 0x1f, 2, bit(t, 7))
+            } else {
+                (t & 0x1f, bit(t, 7), t >> 5 & 0x3)
+            };
+            let (t2, t1, t0) = if c & 0x3 == 0x3 {
+                (2, bit(c, 4), bit(c, 3) << 1 | (bit(c, 2) & !bit(c, 3) & 1))
+            } else if c >> 2 & 0x3 == 0x3 {
+                (2, 2, c & 0x3)
+            } else {
+                (
+                    bit(c, 4),
+                    c >> 2 & 0x3,
+                    bit(c, 1) << 1 | (bit(c, 0) & !bit(c, 1) & 1),
+                )
+            };
+            [t0, t1, t2, t3, t4]
+        }
+
+        // Reads `count` values of an integer sequence with `bits` pulling bits off its front.
+        // The trit bits that a final, partial block of five values leaves out are zero.
+        fn decode_integer_sequence(
+            count: usize,
+            trits: bool,
+            bit_count: u32,
+            mut bits: impl FnMut(u32) -> u32,
+        ) -> Vec<u32> {
+            if !trits {
+                return (0..count).map(|_| bits(bit_count)).collect();
+            }
+            let mut values = vec![];
+            while values.len() < count {
+                let chunk_len = (count - values.len()).min(5);
+                let (mut low_bits, mut packed, mut packed_len) = ([0; 5], 0, 0);
+                for (index, trit_bit_count) in [2, 2, 1, 2, 1].into_iter().enumerate() {
+                    if index < chunk_len {
+                        low_bits[index] = bits(bit_count);
+                        packed |= bits(trit_bit_count) << packed_len;
+                    }
+                    packed_len += trit_bit_count;
+                }
+                let trits = decode_trits(packed);
+                values.extend(
+                    (0..chunk_len).map(|index| trits[index] << bit_count | low_bits[index]),
+                );
+            }
+            values
+        }
+
+        fn integer_sequence_bit_count(count: u32, trits: bool, bit_count: u32) -> u32 {
+            if trits {
+                (count * (8 + 5 * bit_count)).div_ceil(5)
+            } else {
+                count * bit_count
+            }
+        }
+
+        // Unquantizes a weight to the range 0..=64.
+        fn unquantize_weight(value: u32, trits: bool, bit_count: u32) -> u32 {
+            let unquantized = if trits {
+                let (trit, low_bits) = (value >> bit_count, value & ((1 << bit_count) - 1));
+                let a = if low_bits & 1 == 1 { 0x7f } else { 0 };
+                let (b, c) = match bit_count {
+                    0 => return trit * 32,
+                    1 => (0, 50),
+                    2 => ((low_bits >> 1) * 0b100_0101, 23),
+                    _ => ((low_bits >> 1) * 0b010_0001, 11),
+                };
+                (a & 0x20) | ((trit * c + b) ^ a) >> 2
+            } else {
+                let (mut replicated, mut replicated_len) = (0, 0);
+                while replicated_len < 6 {
+                    replicated = replicated << bit_count | value;
+                    replicated_len += bit_count;
+                }
+                replicated >> (replicated_len - 6)
+            };
+            if unquantized > 32 {
+                unquantized + 1
+            } else {
+                unquantized
+            }
+        }
+
+        // Infills the weight of every texel of a block from the weight grid.
+        fn infill_weights(
+            weights: &[u32],
+            grid: &WeightGrid,
+            block_width: u32,
+            block_height: u32,
+        ) -> Vec<u32> {
+            let ds = (1024 + block_width / 2) / (block_width - 1);
+            let dt = (1024 + block_height / 2) / (block_height - 1);
+            let mut infilled = vec![];
+            for t in 0..block_height {
+                for s in 0..block_width {
+                    let gs = (ds * s * (grid.width - 1) + 32) >> 6;
+                    let gt = (dt * t * (grid.height - 1) + 32) >> 6;
+                    let (js, fs, jt, ft) = (gs >> 4, gs & 0xf, gt >> 4, gt & 0xf);
+                    // Points past the edge of the grid always have a weight of zero.
+                    let v0 = (js + jt * grid.width) as usize;
+                    let p = |offset: u32| weights.get(v0 + offset as usize).copied().unwrap_or(0);
+                    let w11 = (fs * ft + 8) >> 4;
+                    let (w10, w01, w00) = (ft - w11, fs - w11, 16 + w11 - fs - ft);
+                    infilled.push(
+                        (p(0) * w00
+                            + p(1) * w01
+                            + p(grid.width) * w10
+                            + p(grid.width + 1) * w11
+                            + 8)
+                            >> 4,
+                    );
+                }
+            }
+            infilled
+        }
+
+        // Decodes the endpoints of HDR endpoint mode 11 to 16-bit logarithmic values.
+        pub(super) fn decode_hdr_rgb_endpoints(v: [u32; 6]) -> [[i32; 3]; 2] {
+            let major_component = (v[4] & 0x80) >> 7 | (v[5] & 0x80) >> 6;
+            if major_component == 3 {
+                return [
+                    [v[0] << 8, v[2] << 8, (v[4] & 0x7f) << 9].map(|value| value as i32),
+                    [v[1] << 8, v[3] << 8, (v[5] & 0x7f) << 9].map(|value| value as i32),
+                ];
+            }
+
+            let mode = (v[1] & 0x80) >> 7 | (v[2] & 0x80) >> 6 | (v[3] & 0x80) >> 5;
+            let mut a = v[0] | (v[1] & 0x40) << 2;
+            let (mut b0, mut b1, mut c) = (v[2] & 0x3f, v[3] & 0x3f, v[1] & 0x3f);
+            let (mut d0, mut d1) = (v[4] & 0x7f, v[5] & 0x7f);
+            let d_bits = [7, 6, 7, 6, 5, 6, 5, 6][mode as usize];
+
+            // Six bits whose meaning depends on the mode.
+            let bit0 = v[2] >> 6 & 1;
+            let bit1 = v[3] >> 6 & 1;
+            let bit2 = v[4] >> 6 & 1;
+            let bit3 = v[5] >> 6 & 1;
+            let bit4 = v[4] >> 5 & 1;
+            let bit5 = v[5] >> 5 & 1;
+            let one_hot_mode = 1 << mode;
+            if one_hot_mode & 0xa4 != 0 {
+                a |= bit0 << 9;
+            }
+            if one_hot_mode & 0x8 != 0 {
+                a |= bit2 << 9;
+            }
+            if one_hot_mode & 0x50 != 0 {
+                a |= bit4 << 9 | bit5 << 10;
+            }
+            if one_hot_mode & 0xa0 != 0 {
+                a |= bit1 << 10;
+            }
+            if one_hot_mode & 0xc0 != 0 {
+                a |= bit2 << 11;
+            }
+            if one_hot_mode & 0x4 != 0 {
+                c |= bit1 << 6;
+            }
+            if one_hot_mode & 0xe8 != 0 {
+                c |= bit3 << 6;
+            }
+            if one_hot_mode & 0x20 != 0 {
+                c |= bit2 << 7;
+            }
+            if one_hot_mode & 0x5b != 0 {
+                b0 |= bit0 << 6;
+                b1 |= bit1 << 6;
+            }
+            if one_hot_mode & 0x12 != 0 {
+                b0 |= bit2 << 7;
+                b1 |= bit3 << 7;
+            }
+            if one_hot_mode & 0xaf != 0 {
+                d0 |= bit4 << 5;
+                d1 |= bit5 << 5;
+            }
+            if one_hot_mode & 0x5 != 0 {
+                d0 |= bit2 << 6;
+                d1 |= bit3 << 6;
+            }
+
+            // Sign-extend the d fields and scale everything to 12 bits.
+            let sign_extend = |value: u32| ((value << (32 - d_bits)) as i32) >> (32 - d_bits);
+            let shift = (mode >> 1) ^ 3;
+            let [a, b0, b1, c] = [a, b0, b1, c].map(|value| (value as i32) << shift);
+            let [d0, d1] = [d0, d1].map(|value| sign_extend(value) << shift);
+
+            let mut endpoints = [
+                [a - c, a - b0 - c - d0, a - b1 - c - d1],
+                [a, a - b0, a - b1],
+            ]
+            .map(|endpoint| endpoint.map(|value| value.clamp(0, 0xfff)));
+            for endpoint in &mut endpoints {
+                match major_component {
+                    1 => endpoint.swap(0, 1),
+                    2 => endpoint.swap(0, 2),
+                    _ => {}
+                }
+            }
+            endpoints.map(|endpoint| endpoint.map(|value| value << 4))
+        }
+
+        // Converts an interpolated 16-bit logarithmic value to a half float's bits.
+        fn lns_to_half(lns: u32) -> u16 {
+            let (exponent, mantissa) = (lns >> 11, lns & 0x7ff);
+            let mantissa = if mantissa < 512 {
+                mantissa * 3
+            } else if mantissa < 1536 {
+                mantissa * 4 - 512
+            } else {
+                mantissa * 5 - 2048
+            };
+            (exponent << 10 | mantissa >> 3).min(0x7bff) as u16
+        }
+
+        // Decodes a single-partition block with HDR endpoint mode 11, or an HDR void-extent
+        // block, to linear RGB values.
+        pub(super) fn decode_block(
+            block: u128,
+            block_width: u32,
+            block_height: u32,
+        ) -> Vec<[f32; 3]> {
+            let texel_count = (block_width * block_height) as usize;
+            if bits(block, 0, 9) == 0x1fc {
+                assert_eq!(bits(block, 9, 1), 1, "Expected an HDR void-extent block");
+                let color = [0, 1, 2].map(|channel| {
+                    f16::from_bits(bits(block, 64 + channel * 16, 16) as u16).to_f32()
+                });
+                return vec![color; texel_count];
+            }
+
+            let grid = decode_block_mode(block);
+            assert_eq!(bits(block, 11, 2), 0, "Expected a single partition");
+            assert_eq!(bits(block, 13, 4), 11, "Expected HDR endpoint mode 11");
+
+            // The color values take the largest range that fits in the bits the weights leave,
+            // which is only unquantized 8-bit values if six of them fit.
+            let weight_count = grid.width * grid.height;
+            let weight_bits = integer_sequence_bit_count(weight_count, grid.trits, grid.bit_count);
+            assert!(
+                128 - 17 - weight_bits >= 6 * 8,
+                "Expected 8-bit color values"
+            );
+            let values = [0, 1, 2, 3, 4, 5].map(|index| bits(block, 17 + index * 8, 8));
+            let endpoints = decode_hdr_rgb_endpoints(values);
+
+            // The weights are stored from the top of the block down, with their bits reversed.
+            let mut weight_data = block.reverse_bits();
+            let weights = decode_integer_sequence(
+                weight_count as usize,
+                grid.trits,
+                grid.bit_count,
+                |count| {
+                    let value = bits(weight_data, 0, count);
+                    weight_data >>= count;
+                    value
+                },
+            );
+            let weights: Vec<u32> = weights
+                .into_iter()
+                .map(|weight| unquantize_weight(weight, grid.trits, grid.bit_count))
+                .collect();
+
+            infill_weights(&weights, &grid, block_width, block_height)
+                .into_iter()
+                .map(|weight| {
+                    let weight = weight as i32;
+                    [0, 1, 2].map(|channel| {
+                        let lns = (endpoints[0][channel] * (64 - weight)
+                            + endpoints[1][channel] * weight
+                            + 32)
+                            >> 6;
+                        f16::from_bits(lns_to_half(lns as u32)).to_f32()
+                    })
+                })
+                .collect()
+        }
+    }
+
+    fn decode_image(encoded: &[u8], width: u32, height: u32, block_size: u32) -> Vec<[f32; 3]> {
+        let blocks_x = width.div_ceil(block_size);
+        let mut pixels = vec![[0.0; 3]; (width * height) as usize];
+        for (block_index, block) in encoded.chunks_exact(16).enumerate() {
+            let block = u128::from_le_bytes(block.try_into().unwrap());
+            let (block_x, block_y) = (block_index as u32 % blocks_x, block_index as u32 / blocks_x);
+            for (texel_index, texel) in reference::decode_block(block, block_size, block_size)
+                .into_iter()
+                .enumerate()
+            {
+                let x = block_x * block_size + texel_index as u32 % block_size;
+                let y = block_y * block_size + texel_index as u32 / block_size;
+                if x < width && y < height {
+                    pixels[(y * width + x) as usize] = texel;
+                }
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn trit_encodings_round_trip() {
+        let encoder = Encoder::new(4, 4);
+        for index in 0..243 {
+            let trits = reference::decode_trits(encoder.trit_encodings[index] as u32);
+            let decoded_index = trits
+                .iter()
+                .rev()
+                .fold(0, |index, &trit| index * 3 + trit as usize);
+            assert_eq!(decoded_index, index);
+        }
+    }
+
+    #[test]
+    fn block_mode_is_a_4x4_grid_of_12_weight_levels() {
+        let grid = reference::decode_block_mode(BLOCK_MODE);
+        assert_eq!((grid.width, grid.height), (4, 4));
+        assert!(grid.trits);
+        assert_eq!(grid.bit_count, 2);
+    }
+
+    #[test]
+    fn endpoints_round_trip() {
+        let targets = [
+            [[0, 0, 0], [0, 0, 0]],
+            [[0x300, 0x310, 0x320], [0x800, 0x7f0, 0x810]],
+            [[0x100, 0x500, 0x200], [0x400, 0xa00, 0x300]],
+            [[0x700, 0x700, 0x900], [0x780, 0x780, 0xc00]],
+            [
+                [0x010, 0x020, 0x030],
+                [MAX_ENDPOINT, MAX_ENDPOINT, MAX_ENDPOINT],
+            ],
+        ];
+        for target in targets {
+            let encoded = encode_endpoints(target);
+            assert_eq!(
+                reference::decode_hdr_rgb_endpoints(encoded.values.map(u32::from)),
+                encoded.decoded
+            );
+            for (decoded, target) in encoded.decoded.iter().zip(target.iter()) {
+                for (decoded, target) in decoded.iter().zip(target.iter()) {
+                    assert!(
+                        (decoded - (target << 4)).abs() <= 32 << 4,
+                        "{:x?} decoded to {:x?}",
+                        target,
+                        encoded.decoded
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn endpoint_decoding_matches_the_reference() {
+        // Cover every mode and major component with a simple pseudo-random sequence.
+        let mut state = 1u32;
+        for _ in 0..10_000 {
+            let values = [0; 6].map(|_: u8| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            });
+            assert_eq!(
+                decode_endpoints(&values),
+                reference::decode_hdr_rgb_endpoints(values.map(u32::from)),
+                "{:x?}",
+                values
+            );
+        }
+    }
+
+    #[test]
+    fn smooth_gradient_round_trip() {
+        for block_size in [4, 6] {
+            let (width, height) = (12, 12);
+            let mut pixels = vec![];
+            for y in 0..height {
+                for x in 0..width {
+                    let t = (x + y) as f32 / (width + height) as f32;
+                    pixels.extend_from_slice(&[1.0 + t, 0.5 + t * 0.5, 4.0 - t * 2.0, 1.0]);
+                }
+            }
+
+            let encoded = encode_hdr(&pixels, width, height, block_size, block_size);
+            assert_eq!(encoded.len(), ((width / block_size).pow(2) * 16) as usize);
+
+            let decoded = decode_image(&encoded, width, height, block_size);
+            for (texel, pixel) in decoded.iter().zip(pixels.chunks_exact(4)) {
+                for channel in 0..3 {
+                    let error = (texel[channel] / pixel[channel]).log2().abs();
+                    assert!(error < 0.05, "{:?} decoded to {:?}", pixel, texel);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn constant_blocks_are_void_extent() {
+        let pixels = [0.25, 3.0, 1000.0, 0.5].repeat(36);
+        let encoded = encode_hdr(&pixels, 6, 6, 6, 6);
+        let block = u128::from_le_bytes(encoded.try_into().unwrap());
+        assert_eq!(block & 0xffff_ffff_ffff_ffff, VOID_EXTENT_HDR);
+        assert_eq!(
+            reference::decode_block(block, 6, 6),
+            vec![[0.25, 3.0, 1000.0]; 36]
+        );
+    }
+
+    #[test]
+    fn void_extent_matches_astcenc() {
+        // What `astcenc -ch` writes for a 4×4 block filled with (1.0, 0.5, 0.25, 1.0).
+        let expected = [
+            0xfc, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x3c, 0x00, 0x38, 0x00, 0x34,
+            0x00, 0x3c,
+        ];
+        let pixels = [1.0, 0.5, 0.25, 1.0].repeat(16);
+        assert_eq!(encode_hdr(&pixels, 4, 4, 4, 4), expected);
+    }
+
+    #[test]
+    fn negative_and_nan_values_are_flushed_to_zero() {
+        let pixels = [-1.0, f32::NAN, 0.0, 1.0].repeat(16);
+        let block = u128::from_le_bytes(encode_hdr(&pixels, 4, 4, 4, 4).try_into().unwrap());
+        assert_eq!(reference::decode_block(block, 4, 4), vec![[0.0; 3]; 16]);
+    }
+}
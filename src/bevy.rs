@@ -0,0 +1,123 @@
+// gltf-ibl-sampler-egui/src/bevy.rs
+
+use crate::generator::{
+    BevyBundle, BevyVersion, ContainerFormat, Distribution, Output, Projection, TargetFormat,
+};
+use crate::staging::Staging;
+use anyhow::Error;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+
+/// The outputs that a Bevy snippet refers to.
+#[derive(Clone, Copy)]
+pub(crate) struct BevySources {
+    pub(crate) diffuse: usize,
+    pub(crate) specular: usize,
+    pub(crate) skybox: Option<usize>,
+}
+
+/// Returns the outputs that Bevy's `EnvironmentMapLight` and `Skybox` components need: a base
+/// color skybox, a diffuse map, and a specular map, all stored as uncompressed KTX2 cubemaps in
+/// the shared-exponent format that Bevy's own example assets use, and named like them.
+pub(crate) fn preset_outputs() -> Vec<Output> {
+    (0..3)
+        .map(|index| Output {
+            container_format: ContainerFormat::Ktx2,
+            projection: Projection::Cubemap,
+            target_format: TargetFormat::R9G9B9E5Ufloat,
+            filename_template: "{stem}_{kind}_{format}.{ext}".to_owned(),
+            ..Output::default_for_index(index)
+        })
+        .collect()
+}
+
+/// Returns true if Bevy can load the given output as a cubemap.
+pub(crate) fn is_bevy_cubemap(output: &Output) -> bool {
+    output.container_format == ContainerFormat::Ktx2
+        && output.projection == Projection::Cubemap
+        && output.target_format.astc_block_size().is_none()
+}
+
+/// Returns the index of the first enabled output with the given distribution.
+pub(crate) fn distribution_output(outputs: &[Output], distribution: Distribution) -> Option<usize> {
+    outputs.iter().position(|output| {
+        output.enabled
+            && output
+                .filter_settings
+                .as_ref()
+                .is_some_and(|filter_settings| filter_settings.distribution == distribution)
+    })
+}
+
+/// Writes a Rust snippet that adds the environment map to a camera to `staging`.
+pub(crate) fn write_snippet(
+    bundle: &BevyBundle,
+    version: BevyVersion,
+    outputs: &[Output],
+    sources: BevySources,
+    staging: &mut Staging,
+) -> Result<(), Error> {
+    let diffuse = asset_path(&outputs[sources.diffuse].out_cubemap.path);
+    let specular = asset_path(&outputs[sources.specular].out_cubemap.path);
+    let skybox = sources
+        .skybox
+        .map(|skybox| asset_path(&outputs[skybox].out_cubemap.path));
+
+    let mut snippet = String::new();
+    writeln!(
+        &mut snippet,
+        "// Environment map for {}, generated by the glTF IBL Sampler.",
+        version
+    )?;
+    writeln!(&mut snippet, "// Add these components to your camera.")?;
+    writeln!(&mut snippet, "EnvironmentMapLight {{")?;
+    writeln!(
+        &mut snippet,
+        "    diffuse_map: asset_server.load({:?}),",
+        diffuse
+    )?;
+    writeln!(
+        &mut snippet,
+        "    specular_map: asset_server.load({:?}),",
+        specular
+    )?;
+    if version != BevyVersion::V0_12 {
+        writeln!(&mut snippet, "    intensity: {:?},", bundle.intensity)?;
+    }
+    writeln!(&mut snippet, "}},")?;
+
+    if let Some(skybox) = skybox {
+        if version == BevyVersion::V0_12 {
+            writeln!(&mut snippet, "Skybox(asset_server.load({:?})),", skybox)?;
+        } else {
+            writeln!(&mut snippet, "Skybox {{")?;
+            writeln!(&mut snippet, "    image: asset_server.load({:?}),", skybox)?;
+            writeln!(&mut snippet, "    brightness: {:?},", bundle.intensity)?;
+            writeln!(&mut snippet, "}},")?;
+        }
+    }
+
+    fs::write(staging.stage(&bundle.snippet_path.path)?, snippet)?;
+    Ok(())
+}
+
+// Bevy loads assets relative to the `assets` directory, so strip everything up to and including
+// that directory if the file is inside it. Otherwise, assume that the file will be moved to the
+// root of the `assets` directory.
+fn asset_path(path: &Path) -> String {
+    let components: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    match components
+        .iter()
+        .rposition(|component| component == "assets")
+    {
+        Some(assets_index) => components[(assets_index + 1)..].join("/"),
+        None => components
+            .last()
+            .map(|name| name.to_string())
+            .unwrap_or_default(),
+    }
+}
@@ -0,0 +1,220 @@
+// gltf-ibl-sampler-egui/src/capture.rs
+
+use log::info;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// How many of the last lines that the sampler printed are included in error reports.
+pub(crate) const RECENT_LINE_COUNT: usize = 20;
+
+#[cfg(unix)]
+const LINE_BUFFERED: c_int = libc::_IOLBF;
+// The Microsoft C runtime treats line buffering as full buffering, so turn buffering off
+// altogether to see lines as they're written.
+#[cfg(windows)]
+const LINE_BUFFERED: c_int = libc::_IONBF;
+
+/// Redirects the process's `stdout` into a pipe and forwards each line written to it to the log
+/// while the native sampler runs.
+///
+/// Dropping the capture restores the original `stdout` and waits for the remaining lines to be
+/// logged.
+pub(crate) struct StdoutCapture {
+    saved_stdout: c_int,
+    reader: Option<JoinHandle<()>>,
+    recent_lines: Arc<Mutex<RecentLines>>,
+}
+
+/// The last few lines of some output, oldest first.
+#[derive(Default)]
+pub(crate) struct RecentLines(VecDeque<String>);
+
+impl StdoutCapture {
+    pub(crate) fn start() -> io::Result<StdoutCapture> {
+        unsafe {
+            let stdout = libc_stdhandle::stdout();
+            let stdout_fd = libc::fileno(stdout);
+            libc::fflush(stdout);
+
+            let mut fds = [0; 2];
+            if create_pipe(&mut fds) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let [read_fd, write_fd] = fds;
+
+            let saved_stdout = libc::dup(stdout_fd);
+            if saved_stdout < 0 || libc::dup2(write_fd, stdout_fd) < 0 {
+                let error = io::Error::last_os_error();
+                for fd in [read_fd, write_fd, saved_stdout] {
+                    if fd >= 0 {
+                        libc::close(fd);
+                    }
+                }
+                return Err(error);
+            }
+            // `stdout` now holds the only write end of the pipe, so the reader sees the end of
+            // the stream as soon as `stdout` is restored.
+            libc::close(write_fd);
+
+            // Pipes are fully buffered by default, which would hold lines back.
+            libc::setvbuf(stdout, ptr::null_mut(), LINE_BUFFERED, 0);
+
+            let recent_lines = Arc::new(Mutex::new(RecentLines::default()));
+            let reader_recent_lines = recent_lines.clone();
+            let reader = thread::spawn(move || {
+                // Keep reading even if the output isn't valid UTF-8, or the pipe would fill up
+                // and block the sampler.
+                let mut reader = BufReader::new(PipeReader(read_fd));
+                let mut line = vec![];
+                while reader
+                    .read_until(b'\n', &mut line)
+                    .is_ok_and(|count| count > 0)
+                {
+                    let text = String::from_utf8_lossy(&line).trim_end().to_owned();
+                    info!("{}", text);
+                    reader_recent_lines.lock().unwrap().push(text);
+                    line.clear();
+                }
+            });
+
+            Ok(StdoutCapture {
+                saved_stdout,
+                reader: Some(reader),
+                recent_lines,
+            })
+        }
+    }
+
+    /// Stops capturing and returns the last lines that were printed.
+    pub(crate) fn finish(mut self) -> Vec<String> {
+        self.stop();
+        let recent_lines = self.recent_lines.lock().unwrap().to_vec();
+        recent_lines
+    }
+
+    fn stop(&mut self) {
+        let Some(reader) = self.reader.take() else {
+            return;
+        };
+
+        unsafe {
+            let stdout = libc_stdhandle::stdout();
+            libc::fflush(stdout);
+            libc::dup2(self.saved_stdout, libc::fileno(stdout));
+            libc::close(self.saved_stdout);
+        }
+
+        drop(reader.join());
+    }
+}
+
+impl Drop for StdoutCapture {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl RecentLines {
+    pub(crate) fn push(&mut self, line: String) {
+        if self.0.len() == RECENT_LINE_COUNT {
+            self.0.pop_front();
+        }
+        self.0.push_back(line);
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<String> {
+        self.0.iter().cloned().collect()
+    }
+}
+
+/// Moves the process's `stdout` to a new file descriptor and points `stdout` at `stderr` instead.
+///
+/// Worker processes use the returned writer to talk to the app, so that nothing else that prints
+/// to `stdout` can interfere.
+pub(crate) fn divert_stdout() -> io::Result<FdWriter> {
+    unsafe {
+        let stdout = libc_stdhandle::stdout();
+        let stdout_fd = libc::fileno(stdout);
+        libc::fflush(stdout);
+
+        let fd = libc::dup(stdout_fd);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::dup2(libc::fileno(libc_stdhandle::stderr()), stdout_fd) < 0 {
+            let error = io::Error::last_os_error();
+            libc::close(fd);
+            return Err(error);
+        }
+        Ok(FdWriter(fd))
+    }
+}
+
+/// An unbuffered writer for a C runtime file descriptor, which is closed on drop.
+pub(crate) struct FdWriter(c_int);
+
+impl Write for FdWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let count =
+            unsafe { libc::write(self.0, buffer.as_ptr() as *const c_void, buffer.len() as _) };
+        if count < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(count as usize)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for FdWriter {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+// The read end of a pipe, as a C runtime file descriptor, which is closed on drop.
+struct PipeReader(c_int);
+
+impl Read for PipeReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let count = unsafe {
+            libc::read(
+                self.0,
+                buffer.as_mut_ptr() as *mut c_void,
+                buffer.len() as _,
+            )
+        };
+        if count < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(count as usize)
+        }
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+#[cfg(unix)]
+unsafe fn create_pipe(fds: &mut [c_int; 2]) -> c_int {
+    libc::pipe(fds.as_mut_ptr())
+}
+
+#[cfg(windows)]
+unsafe fn create_pipe(fds: &mut [c_int; 2]) -> c_int {
+    libc::pipe(fds.as_mut_ptr(), 65536, libc::O_BINARY)
+}
@@ -0,0 +1,81 @@
+// gltf-ibl-sampler-egui/src/cleanup.rs
+
+use log::info;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tempfile::TempPath;
+
+pub(crate) static INPUT_PREFIX: &str = "IblInput";
+// Older versions captured the sampler's output in files with this prefix.
+static STDOUT_LOG_PREFIX: &str = "IblStdoutLogRedirect";
+pub(crate) static INTERMEDIATE_PREFIX: &str = "IblIntermediate";
+
+// Temporary files older than this are assumed to have been left behind by a run that crashed.
+// Another instance of the app might still be using newer ones.
+const STALE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The temporary files that a job creates, which are deleted when the job finishes unless the
+/// user asked to keep them for debugging.
+pub(crate) struct TemporaryFiles {
+    paths: Vec<TempPath>,
+    keep: bool,
+}
+
+impl TemporaryFiles {
+    pub(crate) fn new(keep: bool) -> TemporaryFiles {
+        TemporaryFiles {
+            paths: vec![],
+            keep,
+        }
+    }
+
+    /// Takes ownership of an existing temporary file and returns its path.
+    pub(crate) fn add(&mut self, temp_path: TempPath) -> PathBuf {
+        let path = temp_path.to_path_buf();
+        self.paths.push(temp_path);
+        path
+    }
+}
+
+impl Drop for TemporaryFiles {
+    fn drop(&mut self) {
+        for temp_path in self.paths.drain(..) {
+            if !self.keep {
+                drop(temp_path.close());
+            } else if let Ok(path) = temp_path.keep() {
+                info!("Kept temporary file: {}", path.display());
+            }
+        }
+    }
+}
+
+/// Deletes temporary files that were left behind by runs that crashed.
+pub(crate) fn remove_stale_temporary_files() {
+    let Ok(entries) = fs::read_dir(env::temp_dir()) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if ![INPUT_PREFIX, STDOUT_LOG_PREFIX, INTERMEDIATE_PREFIX]
+            .iter()
+            .any(|prefix| file_name.starts_with(prefix))
+        {
+            continue;
+        }
+
+        let is_stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| {
+                now.duration_since(modified)
+                    .is_ok_and(|age| age > STALE_AGE)
+            });
+        if is_stale && fs::remove_file(entry.path()).is_ok() {
+            info!("Removed stale temporary file: {}", entry.path().display());
+        }
+    }
+}
@@ -0,0 +1,253 @@
+// gltf-ibl-sampler-egui/src/dds.rs
+
+use crate::generator::TargetFormat;
+use crate::texture::Texture;
+use anyhow::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+static DDS_MAGIC: &[u8; 4] = b"DDS ";
+static DX10_FOURCC: &[u8; 4] = b"DX10";
+
+const HEADER_SIZE: u32 = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+
+// Header flags, from the `DDS_HEADER` documentation.
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+// `DDSCAPS2_CUBEMAP` plus all six `DDSCAPS2_CUBEMAP_*` face flags.
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xfe00;
+
+const DXGI_FORMAT_R32G32B32A32_FLOAT: u32 = 2;
+const DXGI_FORMAT_R16G16B16A16_FLOAT: u32 = 10;
+const DXGI_FORMAT_R8G8B8A8_UNORM: u32 = 28;
+const DXGI_FORMAT_R9G9B9E5_SHAREDEXP: u32 = 67;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+/// Writes a cubemap or 2D texture, with all of its mip levels, as a DDS file.
+///
+/// 8-bit textures use a legacy header for compatibility with older readers; floating-point
+/// textures use the DX10 extended header.
+pub(crate) fn write(
+    path: &Path,
+    texture: &Texture,
+    target_format: TargetFormat,
+) -> Result<(), Error> {
+    let (dxgi_format, bytes_per_pixel) = match target_format {
+        TargetFormat::R8G8B8A8Unorm => (DXGI_FORMAT_R8G8B8A8_UNORM, 4),
+        TargetFormat::R9G9B9E5Ufloat => (DXGI_FORMAT_R9G9B9E5_SHAREDEXP, 4),
+        TargetFormat::R16G16B16A16Sfloat => (DXGI_FORMAT_R16G16B16A16_FLOAT, 8),
+        TargetFormat::R32G32B32A32Sfloat => (DXGI_FORMAT_R32G32B32A32_FLOAT, 16),
+        TargetFormat::Astc4x4Sfloat | TargetFormat::Astc6x6Sfloat => {
+            return Err(Error::msg("DDS files can't contain ASTC textures"))
+        }
+    };
+    let legacy = dxgi_format == DXGI_FORMAT_R8G8B8A8_UNORM;
+    let levels = texture.encode(target_format);
+
+    let mut header: Vec<u32> = vec![
+        HEADER_SIZE,
+        DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PITCH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT,
+        texture.height,
+        texture.width,
+        texture.width * bytes_per_pixel,
+        0,
+        levels.len() as u32,
+    ];
+    header.extend([0; 11]);
+
+    // Pixel format.
+    if legacy {
+        header.extend([
+            PIXEL_FORMAT_SIZE,
+            DDPF_RGB | DDPF_ALPHAPIXELS,
+            0,
+            32,
+            0x0000_00ff,
+            0x0000_ff00,
+            0x00ff_0000,
+            0xff00_0000,
+        ]);
+    } else {
+        header.extend([
+            PIXEL_FORMAT_SIZE,
+            DDPF_FOURCC,
+            u32::from_le_bytes(*DX10_FOURCC),
+            0,
+            0,
+            0,
+            0,
+            0,
+        ]);
+    }
+
+    header.extend([
+        DDSCAPS_COMPLEX | DDSCAPS_TEXTURE | DDSCAPS_MIPMAP,
+        if texture.is_cubemap() {
+            DDSCAPS2_CUBEMAP_ALLFACES
+        } else {
+            0
+        },
+        0,
+        0,
+        0,
+    ]);
+
+    if !legacy {
+        header.extend([
+            dxgi_format,
+            D3D10_RESOURCE_DIMENSION_TEXTURE2D,
+            if texture.is_cubemap() {
+                DDS_RESOURCE_MISC_TEXTURECUBE
+            } else {
+                0
+            },
+            1,
+            0,
+        ]);
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(DDS_MAGIC)?;
+    for value in header {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+
+    // Unlike KTX2, DDS stores each face's entire mip chain before moving on to the next face.
+    for face in 0..(texture.face_count() as usize) {
+        for level in &levels {
+            writer.write_all(&level[face])?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Returns a texture with three mip levels, whose texels hold the index of their face.
+    fn texture(face_count: usize) -> Texture {
+        Texture {
+            width: 4,
+            height: 4,
+            levels: [4, 2, 1]
+                .into_iter()
+                .map(|size| {
+                    (0..face_count)
+                        .map(|face| vec![face as f32; size * size * 4])
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    fn write_texture(texture: &Texture, target_format: TargetFormat) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("texture.dds");
+        write(&path, texture, target_format).unwrap();
+        fs::read(path).unwrap()
+    }
+
+    // Returns the 32-bit words of the header that follows the magic number.
+    fn header_words(bytes: &[u8], count: usize) -> Vec<u32> {
+        bytes[4..(4 + count * 4)]
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn floating_point_cubemaps_use_the_dx10_header() {
+        let bytes = write_texture(&texture(6), TargetFormat::R16G16B16A16Sfloat);
+        assert_eq!(&bytes[0..4], DDS_MAGIC);
+        let header = header_words(&bytes, 36);
+
+        assert_eq!(header[0], HEADER_SIZE);
+        assert_eq!(header[1] & DDSD_MIPMAPCOUNT, DDSD_MIPMAPCOUNT);
+        // Height, width, pitch, depth and mip level count.
+        assert_eq!(header[2..7], [4, 4, 4 * 8, 0, 3]);
+        // The pixel format.
+        assert_eq!(header[18], PIXEL_FORMAT_SIZE);
+        assert_eq!(header[19], DDPF_FOURCC);
+        assert_eq!(header[20].to_le_bytes(), *DX10_FOURCC);
+        // The capabilities.
+        assert_eq!(
+            header[26],
+            DDSCAPS_COMPLEX | DDSCAPS_TEXTURE | DDSCAPS_MIPMAP
+        );
+        assert_eq!(header[27], DDSCAPS2_CUBEMAP_ALLFACES);
+        // The DX10 header: format, dimension, flags, array size and more flags.
+        assert_eq!(
+            header[31..36],
+            [
+                DXGI_FORMAT_R16G16B16A16_FLOAT,
+                D3D10_RESOURCE_DIMENSION_TEXTURE2D,
+                DDS_RESOURCE_MISC_TEXTURECUBE,
+                1,
+                0
+            ]
+        );
+
+        let face_size = (16 + 4 + 1) * 8;
+        assert_eq!(bytes.len(), 4 + 124 + 20 + 6 * face_size);
+    }
+
+    #[test]
+    fn faces_are_stored_with_their_whole_mip_chain() {
+        let bytes = write_texture(&texture(6), TargetFormat::R32G32B32A32Sfloat);
+        let data = &bytes[(4 + 124 + 20)..];
+        let face_size = (16 + 4 + 1) * 16;
+        for (face, face_data) in data.chunks_exact(face_size).enumerate() {
+            assert!(face_data
+                .chunks_exact(4)
+                .all(|value| f32::from_le_bytes(value.try_into().unwrap()) == face as f32));
+        }
+    }
+
+    #[test]
+    fn rgba8_textures_use_the_legacy_header() {
+        let bytes = write_texture(&texture(1), TargetFormat::R8G8B8A8Unorm);
+        let header = header_words(&bytes, 31);
+
+        assert_eq!(header[2..7], [4, 4, 4 * 4, 0, 3]);
+        assert_eq!(
+            header[18..26],
+            [
+                PIXEL_FORMAT_SIZE,
+                DDPF_RGB | DDPF_ALPHAPIXELS,
+                0,
+                32,
+                0x0000_00ff,
+                0x0000_ff00,
+                0x00ff_0000,
+                0xff00_0000
+            ]
+        );
+        // Not a cubemap.
+        assert_eq!(header[27], 0);
+        assert_eq!(bytes.len(), 4 + 124 + (16 + 4 + 1) * 4);
+    }
+
+    #[test]
+    fn astc_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("texture.dds");
+        assert!(write(&path, &texture(6), TargetFormat::Astc4x4Sfloat).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+// gltf-ibl-sampler-egui/src/filename.rs
+
+use crate::generator::{ContainerFormat, Distribution, Output, TargetFormat};
+use std::ffi::{OsStr, OsString};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) static DEFAULT_TEMPLATE: &str = "{stem}_{kind}.{ext}";
+
+/// The values of the placeholders that don't depend on the output.
+pub(crate) struct TemplateContext<'a> {
+    pub(crate) stem: &'a OsStr,
+    pub(crate) date: String,
+}
+
+impl<'a> TemplateContext<'a> {
+    pub(crate) fn new(stem: &'a OsStr) -> TemplateContext<'a> {
+        TemplateContext {
+            stem,
+            date: today(),
+        }
+    }
+}
+
+/// Returns the name of the kind of file that the output produces: the output's file suffix, if
+/// it has one, or a name based on its distribution.
+pub(crate) fn kind(output: &Output) -> &str {
+    if let Some(ref file_suffix) = output.file_suffix {
+        return file_suffix;
+    }
+    match output
+        .filter_settings
+        .as_ref()
+        .map(|filter_settings| filter_settings.distribution)
+    {
+        None => "cubemap",
+        Some(Distribution::Lambertian) => "diffuse",
+        Some(Distribution::Ggx) => "specular",
+        Some(Distribution::Charlie) => "charlie",
+    }
+}
+
+/// Expands the placeholders in the output's file name template. Placeholders that we don't know
+/// about are left as they are.
+///
+/// The stem of the input file is copied as it is, so the result is only valid Unicode if the
+/// stem is.
+///
+/// `kind` and `extension` are passed separately so that the lookup table can be named after the
+/// cubemap it comes from.
+pub(crate) fn expand_template(
+    output: &Output,
+    context: &TemplateContext,
+    kind: &str,
+    extension: &str,
+) -> OsString {
+    let distribution = match output.filter_settings {
+        None => "none",
+        Some(ref filter_settings) => match filter_settings.distribution {
+            Distribution::Lambertian => "lambertian",
+            Distribution::Ggx => "ggx",
+            Distribution::Charlie => "charlie",
+        },
+    };
+    let resolution = match output.cubemap_resolution {
+        None => "auto".to_owned(),
+        Some(resolution) => resolution.to_string(),
+    };
+    let format = match output.container_format {
+        ContainerFormat::Ktx2 | ContainerFormat::Dds => format_name(output.target_format),
+        ContainerFormat::ExrMultiPart | ContainerFormat::ExrPerLevel => "rgba32f",
+        ContainerFormat::HdrPerLevel => "rgbe",
+    };
+    let sample_count = output
+        .filter_settings
+        .as_ref()
+        .map_or(0, |filter_settings| filter_settings.sample_count)
+        .to_string();
+
+    let template = if output.filename_template.is_empty() {
+        DEFAULT_TEMPLATE
+    } else {
+        &output.filename_template
+    };
+
+    let mut file_name = OsString::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        file_name.push(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        file_name.push(match &rest[1..end] {
+            "stem" => context.stem,
+            "kind" => kind.as_ref(),
+            "distribution" => distribution.as_ref(),
+            "resolution" => resolution.as_ref(),
+            "format" => format.as_ref(),
+            "samples" => sample_count.as_ref(),
+            "date" => context.date.as_ref(),
+            "ext" => extension.as_ref(),
+            _ => rest[..=end].as_ref(),
+        });
+        rest = &rest[(end + 1)..];
+    }
+    file_name.push(rest);
+    file_name
+}
+
+fn format_name(target_format: TargetFormat) -> &'static str {
+    match target_format {
+        TargetFormat::R8G8B8A8Unorm => "rgba8",
+        TargetFormat::R9G9B9E5Ufloat => "rgb9e5",
+        TargetFormat::R16G16B16A16Sfloat => "rgba16f",
+        TargetFormat::R32G32B32A32Sfloat => "rgba32f",
+        TargetFormat::Astc4x4Sfloat => "astc4x4",
+        TargetFormat::Astc6x6Sfloat => "astc6x6",
+    }
+}
+
+// Returns today's date in UTC as `YYYY-MM-DD`.
+fn today() -> String {
+    date(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs()),
+    )
+}
+
+// Returns the UTC date `seconds` after the Unix epoch as `YYYY-MM-DD`, using the algorithm from
+// Howard Hinnant's `civil_from_days`.
+fn date(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::FilterSettings;
+
+    fn context() -> TemplateContext<'static> {
+        TemplateContext {
+            stem: OsStr::new("studio"),
+            date: "2024-02-29".to_owned(),
+        }
+    }
+
+    #[test]
+    fn default_template_uses_stem_kind_and_extension() {
+        let output = Output::default_for_index(2);
+        let file_name = expand_template(&output, &context(), kind(&output), "ktx2");
+        assert_eq!(file_name, "studio_specular.ktx2");
+    }
+
+    #[test]
+    fn every_placeholder_is_expanded() {
+        let output = Output {
+            filename_template:
+                "{stem}-{kind}-{distribution}-{resolution}-{format}-{samples}-{date}.{ext}"
+                    .to_owned(),
+            cubemap_resolution: Some(256),
+            target_format: TargetFormat::Astc4x4Sfloat,
+            filter_settings: Some(FilterSettings {
+                distribution: Distribution::Charlie,
+                sample_count: 64,
+                ..FilterSettings::default()
+            }),
+            ..Output::default()
+        };
+        let file_name = expand_template(&output, &context(), "sheen", "ktx2");
+        assert_eq!(
+            file_name,
+            "studio-sheen-charlie-256-astc4x4-64-2024-02-29.ktx2"
+        );
+    }
+
+    #[test]
+    fn unknown_and_unclosed_placeholders_are_kept() {
+        let output = Output {
+            filename_template: "{stem}_{unknown}_{ext".to_owned(),
+            ..Output::default()
+        };
+        let file_name = expand_template(&output, &context(), "cubemap", "ktx2");
+        assert_eq!(file_name, "studio_{unknown}_{ext");
+    }
+
+    #[test]
+    fn empty_template_means_the_default() {
+        let output = Output {
+            filename_template: String::new(),
+            container_format: ContainerFormat::HdrPerLevel,
+            ..Output::default()
+        };
+        let file_name = expand_template(&output, &context(), kind(&output), "hdr");
+        assert_eq!(file_name, "studio_cubemap.hdr");
+    }
+
+    #[test]
+    fn file_suffix_replaces_the_kind() {
+        let output = Output {
+            file_suffix: Some("env".to_owned()),
+            ..Output::default_for_index(1)
+        };
+        assert_eq!(kind(&output), "env");
+    }
+
+    #[test]
+    fn dates_are_converted_from_unix_time() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(951_868_800), "2000-03-01");
+        assert_eq!(date(1_709_251_199), "2024-02-29");
+        assert_eq!(date(4_107_542_400), "2100-03-01");
+    }
+
+    #[test]
+    fn today_is_a_date() {
+        let today = today();
+        assert_eq!(today.len(), 10);
+        assert_eq!(today.as_bytes()[4], b'-');
+        assert_eq!(today.as_bytes()[7], b'-');
+        assert!(today.as_str() >= "2024-01-01");
+    }
+}
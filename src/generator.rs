@@ -1,5 +1,6 @@
 // gltf-ibl-sampler-egui/src/generator.rs
 
+use crate::bevy::{self, BevySources};
 use crate::bindgen::{
     self, IBLLib_Distribution_Charlie, IBLLib_Distribution_GGX, IBLLib_Distribution_Lambertian,
     IBLLib_Distribution_None, IBLLib_OutputFormat_B9G9R9E5_UFLOAT,
@@ -10,37 +11,76 @@ use crate::bindgen::{
     IBLLib_Result_StbError, IBLLib_Result_Success, IBLLib_Result_VulkanError,
     IBLLib_Result_VulkanInitializationFailed,
 };
-use crate::ToLocalizedString;
+use crate::capture::StdoutCapture;
+use crate::cleanup::{self, TemporaryFiles};
+use crate::ktx2::{Ktx2Texture, VK_FORMAT_ASTC_4x4_SFLOAT_BLOCK, VK_FORMAT_ASTC_6x6_SFLOAT_BLOCK};
+use crate::manifest::{self, Backend, BuildKey, InputInfo, Manifest};
+use crate::staging::Staging;
+use crate::texture::{self, Texture};
+use crate::worker::{self, WorkerHandle};
+use crate::{
+    dds, filename, gltf, job_file, openexr, os_path, projection, radiance, ToLocalizedString,
+};
 use anyhow::Error;
 use derive_more::Display;
 use egui::Context;
 use image::imageops::FilterType;
 use image::io::Reader;
 use image::{DynamicImage, ImageBuffer};
-use log::info;
+use log::warn;
 use rust_i18n::t;
 use serde::{Deserialize, Serialize};
+use std::env;
 use std::ffi::{CString, OsStr};
-use std::fs::File;
-use std::io::{BufRead, BufReader, Cursor, Read, Write};
-use std::os::raw::{c_char, c_int, c_void};
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{BufReader, Cursor, Read, Write};
+use std::os::raw::{c_int, c_uint, c_void};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::{ptr, slice, thread};
+use std::time::{Duration, Instant};
+use std::{iter, ptr, slice, thread};
 use tempfile::{Builder, NamedTempFile};
 
 const DEFAULT_OUTPUT_COUNT: usize = 3;
 
-#[derive(Clone, Serialize, Deserialize)]
+// Settings that are missing from saved jobs take their default values, so new settings can be
+// added without breaking older jobs. Changes to the meaning of existing settings need a migration
+// in `job_file`.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct Job {
+    /// The version of the job format.
+    #[serde(default = "job_file::unversioned_job_version")]
+    pub(crate) version: u32,
+    #[serde(with = "os_path")]
     pub(crate) input_path: PathBuf,
     pub(crate) max_image_size: u32,
     pub(crate) outputs: Vec<Output>,
+    pub(crate) gltf_export: GltfExport,
+    pub(crate) bevy_bundle: BevyBundle,
+    /// If true, outputs only replace the existing files once every output has been generated.
+    pub(crate) transactional: bool,
+    /// If true, the re-encoded input is kept after the job finishes.
+    pub(crate) keep_temporary_files: bool,
+    /// If true, the job runs in a separate process, so that crashes don't take down the app.
+    pub(crate) run_in_worker: bool,
+    /// If true, outputs are generated even if their manifests show that they're up to date.
+    pub(crate) force_rebuild: bool,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct Output {
+    /// Disabled outputs keep their settings but aren't generated.
+    pub(crate) enabled: bool,
     pub(crate) out_cubemap: OutputPath,
+    /// Replaces the name of the distribution in automatically-chosen file names.
+    pub(crate) file_suffix: Option<String>,
+    /// The pattern that automatically-chosen file names follow. Empty means the default.
+    pub(crate) filename_template: String,
+    pub(crate) projection: Projection,
+    pub(crate) octahedral_settings: OctahedralSettings,
+    pub(crate) container_format: ContainerFormat,
     pub(crate) mip_level_count: Option<u32>,
     pub(crate) cubemap_resolution: Option<u32>,
     pub(crate) target_format: TargetFormat,
@@ -48,36 +88,165 @@ pub(crate) struct Output {
     pub(crate) filter_settings: Option<FilterSettings>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct FilterSettings {
     pub(crate) distribution: Distribution,
     pub(crate) out_lut: OutputPath,
     pub(crate) sample_count: u32,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct GltfExport {
+    pub(crate) mode: GltfExportMode,
+    pub(crate) snippet_path: OutputPath,
+    #[serde(with = "os_path")]
+    pub(crate) gltf_path: PathBuf,
+    /// Rotation around the vertical axis, in degrees.
+    pub(crate) rotation: f32,
+    pub(crate) intensity: f32,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Display, Deserialize, Serialize)]
+pub(crate) enum GltfExportMode {
+    #[default]
+    Disabled,
+    Snippet,
+    Inject,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct BevyBundle {
+    /// The version of Bevy to write a snippet for, or `None` if no snippet should be written.
+    pub(crate) version: Option<BevyVersion>,
+    pub(crate) snippet_path: OutputPath,
+    pub(crate) intensity: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Display, Deserialize, Serialize)]
+pub(crate) enum BevyVersion {
+    #[display(fmt = "Bevy 0.12")]
+    V0_12,
+    #[display(fmt = "Bevy 0.13")]
+    V0_13,
+    #[display(fmt = "Bevy 0.14")]
+    V0_14,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct OctahedralSettings {
+    pub(crate) resolution: Option<u32>,
+    pub(crate) border: u32,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct OutputPath {
+    #[serde(with = "os_path")]
     pub(crate) path: PathBuf,
     pub(crate) automatic_filename: bool,
 }
 
+#[derive(Serialize, Deserialize)]
 pub(crate) enum OutputProgress {
     NotStartedYet,
     InProgress {
         input_reencoding_status: InputReencodingStatus,
         outputs_finished: usize,
         output_count: usize,
+        outputs: Vec<OutputStatus>,
     },
     Succeeded {
         output_count: usize,
+        /// How many outputs were skipped because they're disabled.
+        disabled_count: usize,
+        /// How many outputs were skipped because they were already up to date.
+        unchanged_count: usize,
+        outputs: Vec<OutputStatus>,
     },
     Failed {
-        which_failed: usize,
-        error: OutputError,
+        error: JobError,
+        outputs: Vec<OutputStatus>,
     },
 }
 
-#[derive(Clone, Copy)]
+/// How far along a single output of a job is.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct OutputStatus {
+    pub(crate) state: OutputState,
+    /// How long the output took to generate, once it has finished or failed.
+    pub(crate) elapsed: Option<Duration>,
+    /// When the app heard that the output started running. Worker processes have their own
+    /// clocks, so this is filled in as progress is received.
+    #[serde(skip)]
+    pub(crate) started: Option<Instant>,
+    /// How far the sampler has got with the output, once it has reported any progress.
+    pub(crate) sampler_progress: Option<SamplerProgress>,
+}
+
+/// The last face that the sampler finished filtering.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub(crate) struct SamplerProgress {
+    /// The mip level that the face belongs to, starting with the largest.
+    pub(crate) mip_level: u32,
+    pub(crate) mip_level_count: u32,
+    /// The index of the face within the mip level, in +X, -X, +Y, -Y, +Z, -Z order.
+    pub(crate) face: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum OutputState {
+    Pending,
+    Running,
+    Done(OutputResult),
+    /// The output's files were already generated from the same input and settings, so it was
+    /// skipped. Holds what was written when they were generated.
+    Unchanged(OutputResult),
+    Failed,
+    /// The output is disabled.
+    Skipped,
+}
+
+/// What was actually written for an output, with automatic settings resolved.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct OutputResult {
+    /// The path of the cubemap, or of its first mip level for formats with a file per level.
+    #[serde(with = "os_path")]
+    pub(crate) path: PathBuf,
+    /// The total size of every file written for the output, including the lookup table.
+    pub(crate) file_size: u64,
+    /// The size of the largest mip level: of each face for cubemaps, and of the whole image for
+    /// other projections.
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) mip_level_count: u32,
+    pub(crate) sample_count: Option<u32>,
+    /// The size of each face of the cubemap that the sampler produced.
+    pub(crate) cubemap_resolution: u32,
+}
+
+// The input image, re-encoded for the sampler.
+struct ReencodedInput {
+    path: CString,
+    info: InputInfo,
+}
+
+// An output that was written to its staged files.
+struct GeneratedOutput {
+    /// The floating-point cubemap that the sampler produced, if it was asked to be kept.
+    cubemap: Option<Texture>,
+    /// The final paths of every file written for the output.
+    files: Vec<PathBuf>,
+    /// True if the sampler wrote the output's file itself, rather than a floating-point cubemap
+    /// that was converted on the CPU.
+    written_by_sampler: bool,
+    result: OutputResult,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub(crate) enum InputReencodingStatus {
     Loading,
     Resizing,
@@ -86,7 +255,7 @@ pub(crate) enum InputReencodingStatus {
 }
 
 #[repr(u32)]
-#[derive(Default, Display)]
+#[derive(Clone, Display, Serialize, Deserialize)]
 pub(crate) enum OutputError {
     VulkanInitializationFailed = IBLLib_Result_VulkanInitializationFailed as u32,
     VulkanError = IBLLib_Result_VulkanError as u32,
@@ -97,11 +266,44 @@ pub(crate) enum OutputError {
     InvalidArgument = IBLLib_Result_InvalidArgument as u32,
     KtxError = IBLLib_Result_KtxError as u32,
     StbError = IBLLib_Result_StbError as u32,
-    #[default]
-    OutputCubemapPathNotValidUTF8,
-    OutputLutPathNotValidUTF8,
     FailedToLoadInput(String),
     FailedToReencodeInput,
+    FailedToReadSamplerOutput(String),
+    FailedToWriteOutput(String),
+    AstcNotSupportedInDds,
+    GltfExportNeedsSpecularOutput,
+    FailedToExportGltf(String),
+    BevyBundleNeedsDiffuseAndSpecularOutputs,
+    BevyBundleNeedsKtx2Cubemaps,
+    FailedToWriteBevySnippet(String),
+    FailedToReplaceOutputs(String),
+    /// Like `FailedToReplaceOutputs`, but some of the files that had already been replaced
+    /// couldn't be put back. The paths are boxed to keep results with this error small.
+    #[display(fmt = "{}", error)]
+    FailedToRestoreOutputs {
+        error: String,
+        paths: Box<[String]>,
+    },
+    FailedToStartWorker(String),
+    /// The worker process exited with the given status before finishing the job.
+    WorkerCrashed(String),
+    WorkerStopped,
+    /// The sampler returned a result code that we don't know about.
+    UnknownNativeResult(IBLLib_Result),
+}
+
+/// An error that stopped a job, along with what we know about where it happened.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct JobError {
+    pub(crate) kind: OutputError,
+    /// The output that was being generated, or `None` if the job failed while exporting.
+    pub(crate) output_index: Option<usize>,
+    #[serde(with = "os_path::option")]
+    pub(crate) path: Option<PathBuf>,
+    /// The result code that the sampler returned, if the error came from it.
+    pub(crate) native_result: Option<IBLLib_Result>,
+    /// The last lines that the sampler printed before the error.
+    pub(crate) log_tail: Vec<String>,
 }
 
 #[derive(Clone, Copy, Default, PartialEq, Display, Deserialize, Serialize)]
@@ -121,6 +323,26 @@ pub(crate) enum TargetFormat {
     #[default]
     R16G16B16A16Sfloat = IBLLib_OutputFormat_R16G16B16A16_SFLOAT,
     R32G32B32A32Sfloat = IBLLib_OutputFormat_R32G32B32A32_SFLOAT,
+    Astc4x4Sfloat = VK_FORMAT_ASTC_4x4_SFLOAT_BLOCK as i32,
+    Astc6x6Sfloat = VK_FORMAT_ASTC_6x6_SFLOAT_BLOCK as i32,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Display, Deserialize, Serialize)]
+pub(crate) enum ContainerFormat {
+    #[default]
+    Ktx2,
+    Dds,
+    ExrMultiPart,
+    ExrPerLevel,
+    HdrPerLevel,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Display, Deserialize, Serialize)]
+pub(crate) enum Projection {
+    #[default]
+    Cubemap,
+    Equirectangular,
+    Octahedral,
 }
 
 struct InputImageWriter {
@@ -131,19 +353,60 @@ struct InputImageWriter {
 impl Default for Job {
     fn default() -> Self {
         Self {
+            version: job_file::JOB_VERSION,
             input_path: PathBuf::new(),
             max_image_size: 4096,
             outputs: (0..DEFAULT_OUTPUT_COUNT)
                 .map(Output::default_for_index)
                 .collect(),
+            gltf_export: GltfExport::default(),
+            bevy_bundle: BevyBundle::default(),
+            transactional: false,
+            keep_temporary_files: false,
+            run_in_worker: false,
+            force_rebuild: false,
+        }
+    }
+}
+
+impl Job {
+    /// Returns what was written for the output the last time that it was generated, if it can be
+    /// skipped because neither the input nor its settings have changed since.
+    ///
+    /// `input_hash` is the hash of the input image, or `None` if it couldn't be hashed, in which
+    /// case nothing is skipped.
+    pub(crate) fn unchanged_output(
+        &self,
+        output_index: usize,
+        input_hash: Option<&str>,
+    ) -> Option<OutputResult> {
+        // Outputs that the glTF light is made from are always generated, since the export needs
+        // their cubemaps.
+        let gltf_source = matches!(
+            self.gltf_export.source_outputs(&self.outputs),
+            Ok(Some((radiance, specular))) if output_index == radiance || output_index == specular
+        );
+        if self.force_rebuild || gltf_source {
+            return None;
         }
+        manifest::up_to_date(&BuildKey::new(
+            self,
+            input_hash?,
+            &self.outputs[output_index],
+        ))
     }
 }
 
 impl Output {
     pub(crate) fn default_for_index(index: usize) -> Self {
         Self {
+            enabled: true,
             out_cubemap: OutputPath::new(),
+            file_suffix: None,
+            filename_template: filename::DEFAULT_TEMPLATE.to_owned(),
+            projection: Projection::Cubemap,
+            octahedral_settings: OctahedralSettings::default(),
+            container_format: ContainerFormat::Ktx2,
             mip_level_count: None,
             cubemap_resolution: None,
             target_format: TargetFormat::R16G16B16A16Sfloat,
@@ -157,6 +420,22 @@ impl Output {
     }
 }
 
+impl Default for Output {
+    fn default() -> Self {
+        Output::default_for_index(0)
+    }
+}
+
+impl Output {
+    /// Lets the app choose the paths of all of the output's files.
+    pub(crate) fn use_automatic_paths(&mut self) {
+        self.out_cubemap.automatic_filename = true;
+        if let Some(ref mut filter_settings) = self.filter_settings {
+            filter_settings.out_lut.automatic_filename = true;
+        }
+    }
+}
+
 impl FilterSettings {
     pub(crate) fn default_for_index(index: usize) -> Self {
         FilterSettings {
@@ -171,6 +450,294 @@ impl FilterSettings {
     }
 }
 
+impl Default for FilterSettings {
+    fn default() -> Self {
+        FilterSettings::default_for_index(1)
+    }
+}
+
+impl Default for GltfExport {
+    fn default() -> Self {
+        GltfExport {
+            mode: GltfExportMode::Disabled,
+            snippet_path: OutputPath::new(),
+            gltf_path: PathBuf::new(),
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl GltfExport {
+    /// Returns the indices of the outputs that the light's radiance and specular images come
+    /// from, or `None` if the light isn't being exported.
+    ///
+    /// The radiance comes from the first unfiltered output if there is one, and from the sharpest
+    /// mip level of the specular output otherwise.
+    pub(crate) fn source_outputs(
+        &self,
+        outputs: &[Output],
+    ) -> Result<Option<(usize, usize)>, OutputError> {
+        if self.mode == GltfExportMode::Disabled {
+            return Ok(None);
+        }
+
+        let specular = bevy::distribution_output(outputs, Distribution::Ggx)
+            .ok_or(OutputError::GltfExportNeedsSpecularOutput)?;
+        let radiance = outputs
+            .iter()
+            .position(|output| output.enabled && output.filter_settings.is_none())
+            .unwrap_or(specular);
+        Ok(Some((radiance, specular)))
+    }
+}
+
+impl Default for BevyBundle {
+    fn default() -> Self {
+        BevyBundle {
+            version: None,
+            snippet_path: OutputPath::new(),
+            intensity: 1000.0,
+        }
+    }
+}
+
+impl BevyBundle {
+    /// Returns the outputs that the snippet refers to, or `None` if no snippet is being written.
+    pub(crate) fn source_outputs(
+        &self,
+        outputs: &[Output],
+    ) -> Result<Option<BevySources>, OutputError> {
+        if self.version.is_none() {
+            return Ok(None);
+        }
+
+        let (Some(diffuse), Some(specular)) = (
+            bevy::distribution_output(outputs, Distribution::Lambertian),
+            bevy::distribution_output(outputs, Distribution::Ggx),
+        ) else {
+            return Err(OutputError::BevyBundleNeedsDiffuseAndSpecularOutputs);
+        };
+        if !bevy::is_bevy_cubemap(&outputs[diffuse]) || !bevy::is_bevy_cubemap(&outputs[specular]) {
+            return Err(OutputError::BevyBundleNeedsKtx2Cubemaps);
+        }
+
+        let skybox = outputs.iter().position(|output| {
+            output.enabled && output.filter_settings.is_none() && bevy::is_bevy_cubemap(output)
+        });
+        Ok(Some(BevySources {
+            diffuse,
+            specular,
+            skybox,
+        }))
+    }
+}
+
+impl Default for OctahedralSettings {
+    fn default() -> Self {
+        OctahedralSettings {
+            resolution: None,
+            border: 1,
+        }
+    }
+}
+
+impl TargetFormat {
+    /// Returns the width and height of a compressed block, if this is an ASTC format.
+    pub(crate) fn astc_block_size(self) -> Option<u32> {
+        match self {
+            TargetFormat::Astc4x4Sfloat => Some(4),
+            TargetFormat::Astc6x6Sfloat => Some(6),
+            _ => None,
+        }
+    }
+}
+
+impl ContainerFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            ContainerFormat::Ktx2 => "ktx2",
+            ContainerFormat::Dds => "dds",
+            ContainerFormat::ExrMultiPart | ContainerFormat::ExrPerLevel => "exr",
+            ContainerFormat::HdrPerLevel => "hdr",
+        }
+    }
+
+    /// Returns false if the container always stores pixels in a fixed floating-point format,
+    /// regardless of the target format.
+    pub(crate) fn uses_target_format(self) -> bool {
+        matches!(self, ContainerFormat::Ktx2 | ContainerFormat::Dds)
+    }
+
+    /// Returns true if each mip level is written to its own file, as named by
+    /// [`texture::mip_level_path`].
+    pub(crate) fn has_file_per_level(self) -> bool {
+        matches!(
+            self,
+            ContainerFormat::ExrPerLevel | ContainerFormat::HdrPerLevel
+        )
+    }
+
+    /// Guesses the container format from a file extension, if it's one we can write.
+    pub(crate) fn from_path(path: &Path) -> Option<ContainerFormat> {
+        let extension = path.extension()?.to_str()?;
+        if extension.eq_ignore_ascii_case("ktx2") {
+            Some(ContainerFormat::Ktx2)
+        } else if extension.eq_ignore_ascii_case("dds") {
+            Some(ContainerFormat::Dds)
+        } else if extension.eq_ignore_ascii_case("exr") {
+            Some(ContainerFormat::ExrMultiPart)
+        } else if extension.eq_ignore_ascii_case("hdr") {
+            Some(ContainerFormat::HdrPerLevel)
+        } else {
+            None
+        }
+    }
+}
+
+impl OutputProgress {
+    /// Returns true if the job has finished, successfully or not.
+    pub(crate) fn is_finished(&self) -> bool {
+        !matches!(
+            *self,
+            OutputProgress::NotStartedYet | OutputProgress::InProgress { .. }
+        )
+    }
+
+    /// Returns the progress of a job that's about to start.
+    fn starting(job: &Job) -> OutputProgress {
+        OutputProgress::InProgress {
+            input_reencoding_status: InputReencodingStatus::Loading,
+            outputs_finished: 0,
+            output_count: job.outputs.len(),
+            outputs: OutputStatus::for_job(job),
+        }
+    }
+
+    /// Returns the status of each output of the job, or nothing if the job hasn't started.
+    pub(crate) fn outputs(&self) -> &[OutputStatus] {
+        match *self {
+            OutputProgress::NotStartedYet => &[],
+            OutputProgress::InProgress { ref outputs, .. }
+            | OutputProgress::Succeeded { ref outputs, .. }
+            | OutputProgress::Failed { ref outputs, .. } => outputs,
+        }
+    }
+
+    fn outputs_mut(&mut self) -> &mut [OutputStatus] {
+        match *self {
+            OutputProgress::NotStartedYet => &mut [],
+            OutputProgress::InProgress {
+                ref mut outputs, ..
+            }
+            | OutputProgress::Succeeded {
+                ref mut outputs, ..
+            }
+            | OutputProgress::Failed {
+                ref mut outputs, ..
+            } => outputs,
+        }
+    }
+}
+
+impl SamplerProgress {
+    /// Returns how much of the sampling is done, from 0 to 1.
+    ///
+    /// Each mip level has a quarter of the texels of the level above it, so it counts for a
+    /// quarter as much.
+    pub(crate) fn fraction(&self) -> f32 {
+        let level_weight = |level: u32| 0.25f32.powi(level as i32);
+        let total: f32 = (0..self.mip_level_count).map(level_weight).sum();
+        let done: f32 = (0..self.mip_level).map(level_weight).sum::<f32>()
+            + level_weight(self.mip_level) * (self.face + 1) as f32 / 6.0;
+        (done / total).min(1.0)
+    }
+}
+
+impl OutputStatus {
+    /// Returns the initial status of each output of the job.
+    fn for_job(job: &Job) -> Vec<OutputStatus> {
+        job.outputs
+            .iter()
+            .map(|output| OutputStatus {
+                state: if output.enabled {
+                    OutputState::Pending
+                } else {
+                    OutputState::Skipped
+                },
+                elapsed: None,
+                started: None,
+                sampler_progress: None,
+            })
+            .collect()
+    }
+
+    /// Marks every output that was running when the job stopped as failed.
+    pub(crate) fn fail_running(outputs: &mut [OutputStatus]) {
+        for output in outputs {
+            if matches!(output.state, OutputState::Running) {
+                output.state = OutputState::Failed;
+            }
+        }
+    }
+}
+
+impl JobError {
+    fn for_output(mut self, output_index: usize) -> JobError {
+        self.output_index = Some(output_index);
+        self
+    }
+
+    fn with_path(mut self, path: &Path) -> JobError {
+        self.path = Some(path.to_owned());
+        self
+    }
+
+    /// Describes the error in plain text, for bug reports.
+    pub(crate) fn details(&self) -> String {
+        let mut details = format!(
+            "{} {}\nError: {}\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            self.kind.to_localized_string()
+        );
+        if let Some(output_index) = self.output_index {
+            details += &format!("Output: {}\n", output_index + 1);
+        }
+        if let Some(ref path) = self.path {
+            details += &format!("Path: {}\n", path.display());
+        }
+        if let Some(native_result) = self.native_result {
+            details += &format!("Sampler result: {}\n", native_result);
+        }
+        if !self.log_tail.is_empty() {
+            details += "Log:\n";
+            for line in &self.log_tail {
+                details += line;
+                details += "\n";
+            }
+        }
+        details
+    }
+}
+
+impl From<OutputError> for JobError {
+    fn from(kind: OutputError) -> JobError {
+        JobError {
+            kind,
+            output_index: None,
+            path: None,
+            native_result: None,
+            log_tail: vec![],
+        }
+    }
+}
+
+// Returns a function that turns an error in writing `path` into a `JobError`.
+fn failed_to_write<E: ToString>(path: &Path) -> impl FnOnce(E) -> JobError + '_ {
+    move |error| JobError::from(OutputError::FailedToWriteOutput(error.to_string())).with_path(path)
+}
+
 impl OutputPath {
     fn new() -> OutputPath {
         OutputPath {
@@ -180,100 +747,300 @@ impl OutputPath {
     }
 }
 
-pub(crate) fn generate(ctx: &Context, job: Job, output_progress: Arc<Mutex<OutputProgress>>) {
+impl Default for OutputPath {
+    fn default() -> Self {
+        OutputPath::new()
+    }
+}
+
+/// Generates all outputs of the job, in a worker process if the job asks for one, and returns a
+/// handle that can stop the worker.
+pub(crate) fn generate(
+    ctx: &Context,
+    job: Job,
+    output_progress: Arc<Mutex<OutputProgress>>,
+) -> WorkerHandle {
     let ctx = (*ctx).clone();
-    let output_count = job.outputs.len();
+    let worker = WorkerHandle::default();
+    let worker_handle = worker.clone();
+
+    // The job counts as in progress from now on, so that nothing else starts in the meantime.
+    *output_progress.lock().unwrap() = OutputProgress::starting(&job);
 
     thread::spawn(move || {
-        let input_path = match reencode_input_image(&ctx, &job, output_count, &output_progress) {
-            Ok(input_path) => input_path,
-            Err(output_error) => {
-                report_output_error(&ctx, &output_progress, 0, output_error);
-                return;
-            }
+        let mut report = |mut progress: OutputProgress| {
+            let mut output_progress = output_progress.lock().unwrap();
+            time_outputs(&output_progress, &mut progress);
+            *output_progress = progress;
+            ctx.request_repaint();
         };
+        if job.run_in_worker {
+            worker::run(&job, &worker_handle, &mut report);
+        } else {
+            run_job(&job, &mut report);
+        }
+    });
 
-        // Redirect `stdout` to a temporary file so we can capture it.
-        let stdout_redirection_file = Builder::new()
-            .prefix("IblStdoutLogRedirect")
-            .suffix(".txt")
-            .tempfile()
-            .ok()
-            .and_then(|temp_file| temp_file.keep().ok())
-            .map(|(_, path)| path);
-        if let Some(stdout_redirection_file) = stdout_redirection_file
-            .as_ref()
-            .and_then(|path| CString::new(path.to_str()?).ok())
-        {
-            unsafe {
-                libc::freopen(
-                    stdout_redirection_file.as_ptr(),
-                    b"w\0".as_ptr() as *const c_char,
-                    libc_stdhandle::stdout(),
-                );
-            }
+    worker
+}
+
+/// Generates all outputs of the job on the current thread, passing each change in progress to
+/// `report`.
+pub(crate) fn run_job(job: &Job, report: &mut dyn FnMut(OutputProgress)) {
+    let output_count = job.outputs.len();
+    let mut outputs = OutputStatus::for_job(job);
+
+    // Everything in here is deleted when the job finishes.
+    let mut temporary_files = TemporaryFiles::new(job.keep_temporary_files);
+
+    let input = match reencode_input_image(job, &outputs, report, &mut temporary_files) {
+        Ok(input) => input,
+        Err(error) => {
+            let error = JobError::from(error).with_path(&job.input_path);
+            report_error(report, error.for_output(0), outputs, None);
+            return;
         }
+    };
+
+    // Forward everything that the sampler prints to the log until the job finishes.
+    let stdout_capture = match StdoutCapture::start() {
+        Ok(stdout_capture) => Some(stdout_capture),
+        Err(error) => {
+            warn!("Failed to capture the sampler's output: {}", error);
+            None
+        }
+    };
+
+    // Outputs whose manifests match the input and their settings are skipped. If the input can't
+    // be hashed, everything is generated.
+    let input_hash = manifest::hash_file(&job.input_path)
+        .map_err(|error| warn!("Failed to hash the input: {}", error))
+        .ok();
+
+    // Check that the exports can be made from these outputs before doing any work.
+    let sources = job
+        .gltf_export
+        .source_outputs(&job.outputs)
+        .and_then(|gltf_sources| Ok((gltf_sources, job.bevy_bundle.source_outputs(&job.outputs)?)));
+    let (gltf_sources, bevy_sources) = match sources {
+        Ok(sources) => sources,
+        Err(error) => {
+            report_error(report, JobError::from(error), outputs, stdout_capture);
+            return;
+        }
+    };
+
+    // Each output is written to temporary files and moved into place once it's complete. In
+    // a transactional job, nothing is moved into place until everything is complete.
+    let mut job_staging = Staging::new();
+
+    // Keep the cubemaps that the glTF light is made from.
+    let mut kept_cubemaps: Vec<Option<Texture>> = vec![];
+    for (output_index, output) in job.outputs.iter().enumerate() {
+        if !output.enabled {
+            kept_cubemaps.push(None);
+            continue;
+        }
+
+        let keep_cubemap = gltf_sources.is_some_and(|(radiance, specular)| {
+            output_index == radiance || output_index == specular
+        });
+
+        if let Some(result) = job.unchanged_output(output_index, input_hash.as_deref()) {
+            outputs[output_index].state = OutputState::Unchanged(result);
+            kept_cubemaps.push(None);
+            report(OutputProgress::InProgress {
+                input_reencoding_status: InputReencodingStatus::Reencoded,
+                outputs_finished: output_index + 1,
+                output_count,
+                outputs: outputs.clone(),
+            });
+            continue;
+        }
+
+        let build_key = BuildKey::new(job, input_hash.as_deref().unwrap_or_default(), output);
+        let mut output_staging = Staging::new();
+        let staging = if job.transactional {
+            &mut job_staging
+        } else {
+            &mut output_staging
+        };
+
+        outputs[output_index].state = OutputState::Running;
+        report(OutputProgress::InProgress {
+            input_reencoding_status: InputReencodingStatus::Reencoded,
+            outputs_finished: output_index,
+            output_count,
+            outputs: outputs.clone(),
+        });
+
+        let mut report_sampler_progress = |sampler_progress| {
+            outputs[output_index].sampler_progress = Some(sampler_progress);
+            report(OutputProgress::InProgress {
+                input_reencoding_status: InputReencodingStatus::Reencoded,
+                outputs_finished: output_index,
+                output_count,
+                outputs: outputs.clone(),
+            });
+        };
 
-        for (output_index, output) in job.outputs.iter().enumerate() {
-            if let Err(error) = generate_one_output(output, &input_path) {
-                maybe_log_stdout_redirection_file(stdout_redirection_file);
-                report_output_error(&ctx, &output_progress, output_index, error);
+        let start_time = Instant::now();
+        let result = generate_one_output(
+            output,
+            &input,
+            keep_cubemap,
+            staging,
+            &mut report_sampler_progress,
+        )
+        .and_then(|generated_output| {
+            let manifest = Manifest::new(
+                build_key,
+                job,
+                input.info.clone(),
+                Backend::new(job, output, generated_output.written_by_sampler),
+                &generated_output.files,
+                generated_output.result.clone(),
+            );
+            manifest::write(&manifest, staging)
+                .map_err(failed_to_write(&output.out_cubemap.path))?;
+            Ok(generated_output)
+        })
+        .and_then(|generated_output| {
+            output_staging
+                .commit()
+                .map_err(failed_to_write(&output.out_cubemap.path))?;
+            Ok(generated_output)
+        });
+        outputs[output_index].elapsed = Some(start_time.elapsed());
+        match result {
+            Ok(generated_output) => {
+                outputs[output_index].state = OutputState::Done(generated_output.result);
+                kept_cubemaps.push(generated_output.cubemap);
+            }
+            Err(error) => {
+                outputs[output_index].state = OutputState::Failed;
+                let error = error.for_output(output_index);
+                report_error(report, error, outputs, stdout_capture);
                 return;
             }
+        }
+
+        if output_index + 1 != output_count {
+            report(OutputProgress::InProgress {
+                input_reencoding_status: InputReencodingStatus::Reencoded,
+                outputs_finished: output_index + 1,
+                output_count,
+                outputs: outputs.clone(),
+            });
+        }
+    }
 
-            if output_index + 1 != output_count {
-                set_output_progress(
-                    &ctx,
-                    &output_progress,
-                    OutputProgress::InProgress {
-                        input_reencoding_status: InputReencodingStatus::Reencoded,
-                        outputs_finished: output_index + 1,
-                        output_count,
-                    },
-                );
+    if let Some((radiance, specular)) = gltf_sources {
+        if let (Some(radiance), Some(specular)) =
+            (&kept_cubemaps[radiance], &kept_cubemaps[specular])
+        {
+            let mut export_staging = Staging::new();
+            let staging = if job.transactional {
+                &mut job_staging
+            } else {
+                &mut export_staging
+            };
+            if let Err(error) = gltf::export(&job.gltf_export, radiance, specular, staging)
+                .and_then(|()| Ok(export_staging.commit()?))
+            {
+                let path = match job.gltf_export.mode {
+                    GltfExportMode::Snippet => &job.gltf_export.snippet_path.path,
+                    _ => &job.gltf_export.gltf_path,
+                };
+                let error = JobError::from(OutputError::FailedToExportGltf(error.to_string()));
+                report_error(report, error.with_path(path), outputs, stdout_capture);
+                return;
             }
         }
+    }
 
-        maybe_log_stdout_redirection_file(stdout_redirection_file);
+    if let (Some(version), Some(bevy_sources)) = (job.bevy_bundle.version, bevy_sources) {
+        let mut export_staging = Staging::new();
+        let staging = if job.transactional {
+            &mut job_staging
+        } else {
+            &mut export_staging
+        };
+        if let Err(error) = bevy::write_snippet(
+            &job.bevy_bundle,
+            version,
+            &job.outputs,
+            bevy_sources,
+            staging,
+        )
+        .and_then(|()| Ok(export_staging.commit()?))
+        {
+            let error = JobError::from(OutputError::FailedToWriteBevySnippet(error.to_string()));
+            let error = error.with_path(&job.bevy_bundle.snippet_path.path);
+            report_error(report, error, outputs, stdout_capture);
+            return;
+        }
+    }
 
-        set_output_progress(
-            &ctx,
-            &output_progress,
-            OutputProgress::Succeeded { output_count },
-        );
+    if let Err(error) = job_staging.commit() {
+        let error = if error.unrestored_paths.is_empty() {
+            OutputError::FailedToReplaceOutputs(error.to_string())
+        } else {
+            let paths = error
+                .unrestored_paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            OutputError::FailedToRestoreOutputs {
+                error: error.to_string(),
+                paths,
+            }
+        };
+        report_error(report, JobError::from(error), outputs, stdout_capture);
+        return;
+    }
+
+    let disabled_count = job.outputs.iter().filter(|output| !output.enabled).count();
+    let unchanged_count = outputs
+        .iter()
+        .filter(|output| matches!(output.state, OutputState::Unchanged(_)))
+        .count();
+    report(OutputProgress::Succeeded {
+        output_count: output_count - disabled_count - unchanged_count,
+        disabled_count,
+        unchanged_count,
+        outputs,
     });
 }
 
 fn reencode_input_image(
-    ctx: &Context,
     job: &Job,
-    output_count: usize,
-    output_progress: &Mutex<OutputProgress>,
-) -> Result<CString, OutputError> {
+    outputs: &[OutputStatus],
+    report: &mut dyn FnMut(OutputProgress),
+    temporary_files: &mut TemporaryFiles,
+) -> Result<ReencodedInput, OutputError> {
     // Load image.
     // TODO: We might be able to skip the reencoding part if this is an HDR image already.
-    set_input_reencoding_status(
-        ctx,
-        InputReencodingStatus::Loading,
-        output_count,
-        output_progress,
-    );
+    set_input_reencoding_status(report, InputReencodingStatus::Loading, outputs);
     let mut input_image = load_image(&job.input_path)
         .map_err(|error| OutputError::FailedToLoadInput(error.to_string()))?;
+    let (width, height) = (input_image.width(), input_image.height());
 
     // Resize the image so it fits within the user's requested bounds.
-    set_input_reencoding_status(
-        ctx,
-        InputReencodingStatus::Resizing,
-        output_count,
-        output_progress,
-    );
+    set_input_reencoding_status(report, InputReencodingStatus::Resizing, outputs);
     input_image = input_image.resize(job.max_image_size, job.max_image_size, FilterType::Lanczos3);
 
     // Open temporary file.
     let mut input_image_writer = InputImageWriter {
         temp_file: Builder::new()
-            .prefix(job.input_path.file_stem().unwrap_or(OsStr::new(".tmp")))
+            .prefix(&format!(
+                "{}_{}_",
+                cleanup::INPUT_PREFIX,
+                job.input_path
+                    .file_stem()
+                    .unwrap_or(OsStr::new(""))
+                    .to_string_lossy()
+            ))
             .suffix(".hdr")
             .tempfile()
             .map_err(|error| OutputError::FailedToLoadInput(error.to_string()))?,
@@ -281,12 +1048,7 @@ fn reencode_input_image(
     };
 
     // Use `stb_image_write` to write a `.hdr` image.
-    set_input_reencoding_status(
-        ctx,
-        InputReencodingStatus::Writing,
-        output_count,
-        output_progress,
-    );
+    set_input_reencoding_status(report, InputReencodingStatus::Writing, outputs);
     let input_image = input_image.to_rgba32f();
     let ok = unsafe {
         bindgen::stbi_write_hdr_to_func(
@@ -304,50 +1066,245 @@ fn reencode_input_image(
 
     drop(input_image_writer.temp_file.flush());
 
-    // Persist that temporary file.
-    let (_, input_path) = input_image_writer
-        .temp_file
-        .keep()
+    // Keep the temporary file around until the job finishes.
+    let input_path = temporary_files.add(input_image_writer.temp_file.into_temp_path());
+    let input_path = os_path::to_c_string(&input_path)
         .map_err(|error| OutputError::FailedToLoadInput(error.to_string()))?;
-    let input_path = input_path
-        .as_os_str()
-        .to_str()
-        .expect("Temporary files should be valid UTF-8");
-    let input_path = CString::new(input_path).unwrap();
 
-    set_input_reencoding_status(
-        ctx,
-        InputReencodingStatus::Reencoded,
-        output_count,
-        output_progress,
-    );
-    Ok(input_path)
+    set_input_reencoding_status(report, InputReencodingStatus::Reencoded, outputs);
+    Ok(ReencodedInput {
+        path: input_path,
+        info: InputInfo {
+            width,
+            height,
+            sampled_width: input_image.width(),
+            sampled_height: input_image.height(),
+        },
+    })
 }
 
 fn set_input_reencoding_status(
-    ctx: &Context,
+    report: &mut dyn FnMut(OutputProgress),
     status: InputReencodingStatus,
-    output_count: usize,
-    output_progress: &Mutex<OutputProgress>,
+    outputs: &[OutputStatus],
 ) {
-    set_output_progress(
-        ctx,
-        output_progress,
-        OutputProgress::InProgress {
-            input_reencoding_status: status,
-            outputs_finished: 0,
-            output_count,
-        },
-    );
+    report(OutputProgress::InProgress {
+        input_reencoding_status: status,
+        outputs_finished: 0,
+        output_count: outputs.len(),
+        outputs: outputs.to_vec(),
+    });
+}
+
+/// Generates a single output, writing its files to `staging`. If `keep_cubemap` is true, also
+/// returns the floating-point cubemap that the sampler produced.
+///
+/// The sampler's progress is passed to `report_sampler_progress` as it goes.
+fn generate_one_output(
+    output: &Output,
+    input: &ReencodedInput,
+    keep_cubemap: bool,
+    staging: &mut Staging,
+    report_sampler_progress: &mut dyn FnMut(SamplerProgress),
+) -> Result<GeneratedOutput, JobError> {
+    // File name templates can put outputs in directories that don't exist yet.
+    let lut_path = output
+        .filter_settings
+        .as_ref()
+        .map(|filter_settings| &filter_settings.out_lut.path);
+    for path in iter::once(&output.out_cubemap.path).chain(lut_path) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(failed_to_write(parent))?;
+        }
+    }
+
+    let mut files: Vec<PathBuf> = iter::once(&output.out_cubemap.path)
+        .chain(lut_path)
+        .cloned()
+        .collect();
+
+    let cubemap_path = staging
+        .stage(&output.out_cubemap.path)
+        .map_err(failed_to_write(&output.out_cubemap.path))?;
+    let lut_path = lut_path
+        .map(|lut_path| staging.stage(lut_path).map_err(failed_to_write(lut_path)))
+        .transpose()?;
+    let mut staged_paths: Vec<PathBuf> = iter::once(cubemap_path.clone())
+        .chain(lut_path.clone())
+        .collect();
+
+    if !keep_cubemap
+        && output.projection == Projection::Cubemap
+        && output.container_format == ContainerFormat::Ktx2
+        && output.target_format.astc_block_size().is_none()
+    {
+        run_sampler(
+            output,
+            &input.path,
+            &cubemap_path,
+            lut_path.as_deref(),
+            output.target_format,
+            report_sampler_progress,
+        )?;
+
+        // The sampler chooses the resolution and mip level count if they're automatic.
+        let (width, mip_level_count) =
+            Ktx2Texture::read_dimensions(&cubemap_path).map_err(|error| {
+                JobError::from(OutputError::FailedToReadSamplerOutput(error.to_string()))
+                    .with_path(&output.out_cubemap.path)
+            })?;
+        return Ok(GeneratedOutput {
+            cubemap: None,
+            files,
+            written_by_sampler: true,
+            result: output_result(
+                output,
+                &staged_paths,
+                width,
+                (width, width),
+                mip_level_count,
+            )?,
+        });
+    }
+    if output.container_format == ContainerFormat::Dds
+        && output.target_format.astc_block_size().is_some()
+    {
+        return Err(OutputError::AstcNotSupportedInDds.into());
+    }
+
+    // The sampler can only write cubemaps to KTX2 files in uncompressed formats, so have it write
+    // floating-point data to a temporary file and convert that ourselves.
+    let intermediate_file = Builder::new()
+        .prefix(cleanup::INTERMEDIATE_PREFIX)
+        .suffix(".ktx2")
+        .tempfile()
+        .map_err(failed_to_write(&env::temp_dir()))?;
+    run_sampler(
+        output,
+        &input.path,
+        intermediate_file.path(),
+        lut_path.as_deref(),
+        TargetFormat::R32G32B32A32Sfloat,
+        report_sampler_progress,
+    )?;
+
+    let failed_to_read = |error: String| {
+        JobError::from(OutputError::FailedToReadSamplerOutput(error))
+            .with_path(intermediate_file.path())
+    };
+    let cubemap = Ktx2Texture::read(intermediate_file.path())
+        .and_then(|texture| Texture::from_ktx2(&texture))
+        .map_err(|error| failed_to_read(error.to_string()))?;
+    if !cubemap.is_cubemap() {
+        return Err(failed_to_read("Expected a cubemap".to_owned()));
+    }
+
+    let reprojected;
+    let texture = match output.projection {
+        Projection::Cubemap => &cubemap,
+        Projection::Equirectangular => {
+            reprojected = projection::equirectangular(&cubemap);
+            &reprojected
+        }
+        Projection::Octahedral => {
+            reprojected = projection::octahedral(
+                &cubemap,
+                output
+                    .octahedral_settings
+                    .resolution
+                    .unwrap_or(cubemap.width * 2),
+                output.octahedral_settings.border,
+            );
+            &reprojected
+        }
+    };
+
+    let path = &cubemap_path;
+    match output.container_format {
+        ContainerFormat::Ktx2 => texture.to_ktx2(output.target_format).write(path),
+        ContainerFormat::Dds => dds::write(path, texture, output.target_format),
+        ContainerFormat::ExrMultiPart => openexr::write_multi_part(path, texture),
+        ContainerFormat::ExrPerLevel | ContainerFormat::HdrPerLevel => (0..texture.levels.len())
+            .try_for_each(|level_index| {
+                let level_path = match level_index {
+                    0 => path.clone(),
+                    _ => {
+                        let final_level_path =
+                            texture::mip_level_path(&output.out_cubemap.path, level_index);
+                        let level_path = staging.stage(&final_level_path)?;
+                        staged_paths.push(level_path.clone());
+                        files.push(final_level_path);
+                        level_path
+                    }
+                };
+                if output.container_format == ContainerFormat::ExrPerLevel {
+                    openexr::write_level(&level_path, texture, level_index)
+                } else {
+                    radiance::write_level(&level_path, texture, level_index)
+                }
+            }),
+    }
+    .map_err(failed_to_write(&output.out_cubemap.path))?;
+
+    let result = output_result(
+        output,
+        &staged_paths,
+        cubemap.width,
+        (texture.width, texture.height),
+        texture.levels.len() as u32,
+    )?;
+    Ok(GeneratedOutput {
+        cubemap: keep_cubemap.then_some(cubemap),
+        files,
+        written_by_sampler: false,
+        result,
+    })
+}
+
+// Describes an output whose files have been written to `staged_paths`.
+fn output_result(
+    output: &Output,
+    staged_paths: &[PathBuf],
+    cubemap_resolution: u32,
+    (width, height): (u32, u32),
+    mip_level_count: u32,
+) -> Result<OutputResult, JobError> {
+    let mut file_size = 0;
+    for path in staged_paths {
+        file_size += fs::metadata(path)
+            .map_err(failed_to_write(&output.out_cubemap.path))?
+            .len();
+    }
+
+    Ok(OutputResult {
+        path: output.out_cubemap.path.clone(),
+        file_size,
+        width,
+        height,
+        mip_level_count,
+        sample_count: output
+            .filter_settings
+            .as_ref()
+            .map(|filter_settings| filter_settings.sample_count),
+        cubemap_resolution,
+    })
 }
 
-fn generate_one_output(output: &Output, input_path: &CString) -> Result<(), OutputError> {
-    let cubemap_path = output
-        .out_cubemap
-        .path
-        .to_str()
-        .and_then(|path| CString::new(path).ok())
-        .ok_or(OutputError::OutputCubemapPathNotValidUTF8)?;
+// Writes the cubemap to `cubemap_path` and, for filtered outputs, the lookup table to `lut_path`,
+// or to the output's own lookup table path if that's `None`. The sampler calls
+// `report_progress` each time that it finishes a face of a mip level.
+fn run_sampler(
+    output: &Output,
+    input_path: &CString,
+    cubemap_path: &Path,
+    lut_path: Option<&Path>,
+    target_format: TargetFormat,
+    mut report_progress: &mut dyn FnMut(SamplerProgress),
+) -> Result<(), JobError> {
+    let cubemap_path =
+        os_path::to_c_string(cubemap_path).map_err(failed_to_write(&output.out_cubemap.path))?;
+    let progress_user_data =
+        (&mut report_progress) as *mut &mut dyn FnMut(SamplerProgress) as *mut c_void;
 
     let error = unsafe {
         match output.filter_settings {
@@ -360,18 +1317,17 @@ fn generate_one_output(output: &Output, input_path: &CString) -> Result<(), Outp
                     output.cubemap_resolution.unwrap_or_default(),
                     output.mip_level_count.unwrap_or_default(),
                     0,
-                    output.target_format as _,
+                    target_format as _,
                     output.lod_bias,
                     /*debugOutput=*/ true,
+                    Some(sampler_progress_callback),
+                    progress_user_data,
                 )
             }
             Some(ref filter_settings) => {
-                let lut_path = filter_settings
-                    .out_lut
-                    .path
-                    .to_str()
-                    .and_then(|path| CString::new(path).ok())
-                    .ok_or(OutputError::OutputLutPathNotValidUTF8)?;
+                let lut_path =
+                    os_path::to_c_string(lut_path.unwrap_or(&filter_settings.out_lut.path))
+                        .map_err(failed_to_write(&filter_settings.out_lut.path))?;
 
                 bindgen::IBLLib_sample(
                     input_path.as_ptr(),
@@ -381,9 +1337,11 @@ fn generate_one_output(output: &Output, input_path: &CString) -> Result<(), Outp
                     output.cubemap_resolution.unwrap_or_default(),
                     output.mip_level_count.unwrap_or_default(),
                     filter_settings.sample_count,
-                    output.target_format as _,
+                    target_format as _,
                     output.lod_bias,
                     /*debugOutput=*/ true,
+                    Some(sampler_progress_callback),
+                    progress_user_data,
                 )
             }
         }
@@ -392,48 +1350,37 @@ fn generate_one_output(output: &Output, input_path: &CString) -> Result<(), Outp
     if error == IBLLib_Result_Success {
         Ok(())
     } else {
-        Err(OutputError::from(error))
+        Err(JobError {
+            native_result: Some(error),
+            ..JobError::from(OutputError::from(error)).with_path(&output.out_cubemap.path)
+        })
     }
 }
 
-fn report_output_error(
-    ctx: &Context,
-    output_progress: &Mutex<OutputProgress>,
-    output_index: usize,
-    output_error: OutputError,
-) {
-    set_output_progress(
-        ctx,
-        output_progress,
-        OutputProgress::Failed {
-            which_failed: output_index,
-            error: output_error,
-        },
-    );
-}
-
-fn set_output_progress(
-    ctx: &Context,
-    output_progress_slot: &Mutex<OutputProgress>,
-    output_progress: OutputProgress,
+// Reports the error along with the last lines that the sampler printed, if they were captured.
+fn report_error(
+    report: &mut dyn FnMut(OutputProgress),
+    mut error: JobError,
+    outputs: Vec<OutputStatus>,
+    stdout_capture: Option<StdoutCapture>,
 ) {
-    *output_progress_slot.lock().unwrap() = output_progress;
-    ctx.request_repaint();
-}
-
-fn maybe_log_stdout_redirection_file(stdout_redirection_file: Option<PathBuf>) {
-    // Flush the file and close it so that we can open it on the Rust side.
-    unsafe {
-        let stdout = libc_stdhandle::stdout();
-        libc::fflush(stdout);
-        libc::fclose(stdout);
+    if let Some(stdout_capture) = stdout_capture {
+        error.log_tail = stdout_capture.finish();
     }
+    report(OutputProgress::Failed { error, outputs });
+}
 
-    // Load and log the contents of the temporary file.
-    let Some(stdout_redirection_file) = stdout_redirection_file else { return };
-    let Ok(file) = File::open(stdout_redirection_file) else { return };
-    for line in BufReader::new(file).lines().flatten() {
-        info!("{}", line);
+// Notes when each output started running, which worker processes can't report.
+fn time_outputs(previous_progress: &OutputProgress, progress: &mut OutputProgress) {
+    let previous_outputs = previous_progress.outputs();
+    for (output_index, output) in progress.outputs_mut().iter_mut().enumerate() {
+        if !matches!(output.state, OutputState::Running) {
+            continue;
+        }
+        output.started = match previous_outputs.get(output_index) {
+            Some(previous) if matches!(previous.state, OutputState::Running) => previous.started,
+            _ => Some(Instant::now()),
+        };
     }
 }
 
@@ -448,7 +1395,8 @@ impl From<IBLLib_Result> for OutputError {
             IBLLib_Result_FileNotFound => OutputError::FileNotFound,
             IBLLib_Result_KtxError => OutputError::KtxError,
             IBLLib_Result_StbError => OutputError::StbError,
-            _ => OutputError::InvalidArgument,
+            IBLLib_Result_InvalidArgument => OutputError::InvalidArgument,
+            _ => OutputError::UnknownNativeResult(value),
         }
     }
 }
@@ -505,6 +1453,20 @@ pub(crate) fn load_image(path: &PathBuf) -> Result<DynamicImage, Error> {
     Ok(image)
 }
 
+unsafe extern "C" fn sampler_progress_callback(
+    userdata: *mut c_void,
+    mip_level: c_uint,
+    mip_level_count: c_uint,
+    face: c_uint,
+) {
+    let report_progress = userdata as *mut &mut dyn FnMut(SamplerProgress);
+    (*report_progress)(SamplerProgress {
+        mip_level,
+        mip_level_count,
+        face,
+    });
+}
+
 unsafe extern "C" fn input_file_writer(userdata: *mut c_void, ptr: *mut c_void, len: c_int) {
     let input_image_writer = userdata as *mut InputImageWriter;
     if !(*input_image_writer).ok {
@@ -539,16 +1501,53 @@ impl ToLocalizedString for OutputError {
             OutputError::InvalidArgument => t!("output.error.invalid.argument"),
             OutputError::KtxError => t!("output.error.ktx.error"),
             OutputError::StbError => t!("output.error.stb.error"),
-            OutputError::OutputCubemapPathNotValidUTF8 => {
-                t!("output.error.output.cubemap.path.not.valid.utf8")
-            }
-            OutputError::OutputLutPathNotValidUTF8 => {
-                t!("output.error.output.lut.path.not.valid.utf8")
-            }
             OutputError::FailedToLoadInput(ref error) => {
                 t!("output.error.failed.to.load.input", error = error)
             }
             OutputError::FailedToReencodeInput => t!("output.error.failed.to.reencode.input"),
+            OutputError::FailedToReadSamplerOutput(ref error) => {
+                t!("output.error.failed.to.read.sampler.output", error = error)
+            }
+            OutputError::FailedToWriteOutput(ref error) => {
+                t!("output.error.failed.to.write.output", error = error)
+            }
+            OutputError::AstcNotSupportedInDds => t!("output.error.astc.not.supported.in.dds"),
+            OutputError::GltfExportNeedsSpecularOutput => {
+                t!("output.error.gltf.export.needs.specular.output")
+            }
+            OutputError::FailedToExportGltf(ref error) => {
+                t!("output.error.failed.to.export.gltf", error = error)
+            }
+            OutputError::BevyBundleNeedsDiffuseAndSpecularOutputs => {
+                t!("output.error.bevy.bundle.needs.diffuse.and.specular.outputs")
+            }
+            OutputError::BevyBundleNeedsKtx2Cubemaps => {
+                t!("output.error.bevy.bundle.needs.ktx2.cubemaps")
+            }
+            OutputError::FailedToWriteBevySnippet(ref error) => {
+                t!("output.error.failed.to.write.bevy.snippet", error = error)
+            }
+            OutputError::FailedToReplaceOutputs(ref error) => {
+                t!("output.error.failed.to.replace.outputs", error = error)
+            }
+            OutputError::FailedToRestoreOutputs {
+                ref error,
+                ref paths,
+            } => t!(
+                "output.error.failed.to.restore.outputs",
+                error = error,
+                paths = (paths.join("\n"))
+            ),
+            OutputError::FailedToStartWorker(ref error) => {
+                t!("output.error.failed.to.start.worker", error = error)
+            }
+            OutputError::WorkerCrashed(ref status) => {
+                t!("output.error.worker.crashed", status = status)
+            }
+            OutputError::WorkerStopped => t!("output.error.worker.stopped"),
+            OutputError::UnknownNativeResult(result) => {
+                t!("output.error.unknown.native.result", result = result)
+            }
         }
     }
 }
@@ -571,6 +1570,68 @@ impl ToLocalizedString for TargetFormat {
             TargetFormat::R9G9B9E5Ufloat => t!("output.target.format.9995"),
             TargetFormat::R16G16B16A16Sfloat => t!("output.target.format.16"),
             TargetFormat::R32G32B32A32Sfloat => t!("output.target.format.32"),
+            TargetFormat::Astc4x4Sfloat => t!("output.target.format.astc.4x4"),
+            TargetFormat::Astc6x6Sfloat => t!("output.target.format.astc.6x6"),
         }
     }
 }
+
+impl ToLocalizedString for ContainerFormat {
+    fn to_localized_string(&self) -> String {
+        match *self {
+            ContainerFormat::Ktx2 => t!("output.container.format.ktx2"),
+            ContainerFormat::Dds => t!("output.container.format.dds"),
+            ContainerFormat::ExrMultiPart => t!("output.container.format.exr.multi.part"),
+            ContainerFormat::ExrPerLevel => t!("output.container.format.exr.per.level"),
+            ContainerFormat::HdrPerLevel => t!("output.container.format.hdr.per.level"),
+        }
+    }
+}
+
+impl ToLocalizedString for Projection {
+    fn to_localized_string(&self) -> String {
+        match *self {
+            Projection::Cubemap => t!("output.projection.cubemap"),
+            Projection::Equirectangular => t!("output.projection.equirectangular"),
+            Projection::Octahedral => t!("output.projection.octahedral"),
+        }
+    }
+}
+
+impl ToLocalizedString for GltfExportMode {
+    fn to_localized_string(&self) -> String {
+        match *self {
+            GltfExportMode::Disabled => t!("gltf.export.mode.disabled"),
+            GltfExportMode::Snippet => t!("gltf.export.mode.snippet"),
+            GltfExportMode::Inject => t!("gltf.export.mode.inject"),
+        }
+    }
+}
+
+impl ToLocalizedString for Option<BevyVersion> {
+    fn to_localized_string(&self) -> String {
+        match *self {
+            None => t!("bevy.version.none"),
+            Some(version) => version.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampler_progress_weights_mip_levels_by_size() {
+        let progress = |mip_level, face| SamplerProgress {
+            mip_level,
+            mip_level_count: 3,
+            face,
+        };
+        // The levels have 16, 4 and 1 parts of work, for 21 in all.
+        assert_eq!(progress(0, 2).fraction(), 8.0 / 21.0);
+        assert_eq!(progress(0, 5).fraction(), 16.0 / 21.0);
+        assert_eq!(progress(1, 5).fraction(), 20.0 / 21.0);
+        assert_eq!(progress(2, 5).fraction(), 1.0);
+    }
+}
@@ -0,0 +1,312 @@
+// gltf-ibl-sampler-egui/src/gltf.rs
+
+use crate::generator::{GltfExport, GltfExportMode};
+use crate::projection;
+use crate::staging::Staging;
+use crate::texture::Texture;
+use anyhow::Error;
+use image::{ImageFormat, RgbaImage};
+use serde_json::{json, Map, Value};
+use std::f32::consts::PI;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+static EXTENSION_NAME: &str = "EXT_lights_image_based";
+
+static FACE_NAMES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// The RGBD encoding used by the images, as implemented by Babylon.js, which defined the
+// extension.
+const RGBD_MAX_RANGE: f32 = 255.0;
+const RGBD_GAMMA: f32 = 2.2;
+
+/// Writes an `EXT_lights_image_based` light, either as a standalone snippet or into an existing
+/// `.gltf` file, depending on the export mode.
+///
+/// `radiance` should be an unfiltered cubemap, which is projected onto spherical harmonics for
+/// the irradiance coefficients. Every face of every mip level of `specular` is written as an
+/// RGBD-encoded PNG next to the `.json` or `.gltf` file. All files are written to `staging`.
+pub(crate) fn export(
+    settings: &GltfExport,
+    radiance: &Texture,
+    specular: &Texture,
+    staging: &mut Staging,
+) -> Result<(), Error> {
+    let path = match settings.mode {
+        GltfExportMode::Disabled => return Ok(()),
+        GltfExportMode::Snippet => &settings.snippet_path.path,
+        GltfExportMode::Inject => &settings.gltf_path,
+    };
+
+    let mut document = match settings.mode {
+        GltfExportMode::Inject => serde_json::from_slice(&fs::read(path)?)?,
+        _ => json!({}),
+    };
+    let Some(root) = document.as_object_mut() else {
+        return Err(Error::msg("The glTF file doesn't contain a JSON object"));
+    };
+
+    // Write the specular images and add them to the document. Images from an earlier export to
+    // the same file have the same URIs, so they're reused rather than added again.
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let images = array_entry(root, "images")?;
+    let mut specular_images = vec![];
+    for (level_index, faces) in specular.levels.iter().enumerate() {
+        let (size, _) = specular.level_size(level_index);
+        let mut level_images = vec![];
+        for (face_name, face) in FACE_NAMES.iter().zip(faces.iter()) {
+            let file_name = image_file_name(path, level_index, face_name);
+            RgbaImage::from_raw(size, size, encode_rgbd(face))
+                .unwrap()
+                .save_with_format(
+                    staging.stage(&path.with_file_name(&file_name))?,
+                    ImageFormat::Png,
+                )?;
+
+            let uri = encode_uri(&file_name);
+            let image_index = match images.iter().position(|image| image["uri"] == uri) {
+                Some(image_index) => image_index,
+                None => {
+                    images.push(json!({
+                        "uri": uri,
+                        "mimeType": "image/png",
+                    }));
+                    images.len() - 1
+                }
+            };
+            level_images.push(image_index);
+        }
+        specular_images.push(level_images);
+    }
+
+    // Add the light itself.
+    let half_angle = settings.rotation.to_radians() * 0.5;
+    let light = json!({
+        "name": name,
+        "rotation": [0.0, half_angle.sin(), 0.0, half_angle.cos()],
+        "intensity": settings.intensity,
+        "irradianceCoefficients": irradiance_coefficients(radiance),
+        "specularImageSize": specular.width,
+        "specularImages": specular_images,
+    });
+    let extensions = object_entry(root, "extensions")?;
+    let lights = array_entry(object_entry(extensions, EXTENSION_NAME)?, "lights")?;
+    // Replace the light from an earlier export to the same file, if there is one, rather than
+    // adding another.
+    let light_index = match lights.iter().position(|existing_light| {
+        existing_light["name"] == *name || uses_any_image(existing_light, &specular_images)
+    }) {
+        Some(light_index) => {
+            lights[light_index] = light;
+            light_index
+        }
+        None => {
+            lights.push(light);
+            lights.len() - 1
+        }
+    };
+
+    let extensions_used = array_entry(root, "extensionsUsed")?;
+    if !extensions_used.iter().any(|name| name == EXTENSION_NAME) {
+        extensions_used.push(json!(EXTENSION_NAME));
+    }
+
+    // Point the default scene at the new light.
+    if settings.mode == GltfExportMode::Inject {
+        let scene_index = root.get("scene").and_then(Value::as_u64).unwrap_or(0) as usize;
+        if let Some(scene) = root
+            .get_mut("scenes")
+            .and_then(|scenes| scenes.get_mut(scene_index))
+            .and_then(Value::as_object_mut)
+        {
+            object_entry(scene, "extensions")?
+                .insert(EXTENSION_NAME.to_owned(), json!({ "light": light_index }));
+        }
+    }
+
+    let file = File::create(staging.stage(path)?)?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &document)?;
+    Ok(())
+}
+
+/// Returns the specular images that an earlier export to `path` wrote and that still exist, all
+/// of which are replaced when exporting again.
+pub(crate) fn existing_image_paths(path: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![];
+    for level_index in 0.. {
+        let level_paths: Vec<PathBuf> = FACE_NAMES
+            .iter()
+            .map(|face_name| path.with_file_name(image_file_name(path, level_index, face_name)))
+            .filter(|image_path| image_path.exists())
+            .collect();
+        if level_paths.is_empty() {
+            break;
+        }
+        paths.extend(level_paths);
+    }
+    paths
+}
+
+fn image_file_name(path: &Path, level_index: usize, face_name: &str) -> String {
+    format!(
+        "{}_specular_mip{}_{}.png",
+        path.file_stem().unwrap_or_default().to_string_lossy(),
+        level_index,
+        face_name
+    )
+}
+
+// Projects the first mip level of a cubemap onto the first nine spherical harmonics and
+// convolves the result with the clamped cosine lobe, which yields irradiance.
+fn irradiance_coefficients(radiance: &Texture) -> [[f32; 3]; 9] {
+    let (size, _) = radiance.level_size(0);
+    let mut coefficients = [[0.0; 3]; 9];
+    for (face_index, face) in radiance.levels[0].iter().enumerate() {
+        for (texel_index, pixel) in face.chunks_exact(4).enumerate() {
+            let s = ((texel_index as u32 % size) as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let t = ((texel_index as u32 / size) as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let [x, y, z] = projection::face_direction(face_index, s, t);
+            let length_squared = x * x + y * y + z * z;
+            let length = length_squared.sqrt();
+            let (x, y, z) = (x / length, y / length, z / length);
+
+            let solid_angle = (2.0 / size as f32).powi(2) / (length_squared * length);
+            let basis = [
+                0.282095,
+                0.488603 * y,
+                0.488603 * z,
+                0.488603 * x,
+                1.092548 * x * y,
+                1.092548 * y * z,
+                0.315392 * (3.0 * z * z - 1.0),
+                1.092548 * x * z,
+                0.546274 * (x * x - y * y),
+            ];
+            for (coefficient, basis) in coefficients.iter_mut().zip(basis) {
+                for channel in 0..3 {
+                    coefficient[channel] += pixel[channel] * basis * solid_angle;
+                }
+            }
+        }
+    }
+
+    // Convolve with the cosine lobe, band by band.
+    for (index, coefficient) in coefficients.iter_mut().enumerate() {
+        let band_factor = match index {
+            0 => PI,
+            1..=3 => 2.0 * PI / 3.0,
+            _ => PI / 4.0,
+        };
+        for channel in coefficient.iter_mut() {
+            *channel *= band_factor;
+        }
+    }
+    coefficients
+}
+
+// Returns true if any of the light's specular images is one of the given images.
+fn uses_any_image(light: &Value, images: &[Vec<usize>]) -> bool {
+    light["specularImages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(Value::as_u64)
+        .any(|index| images.iter().flatten().any(|&image| image as u64 == index))
+}
+
+fn encode_rgbd(pixels: &[f32]) -> Vec<u8> {
+    pixels
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let max = pixel[0].max(pixel[1]).max(pixel[2]).max(1.0e-6);
+            let divisor = ((RGBD_MAX_RANGE / max).max(1.0).floor() / 255.0).clamp(0.0, 1.0);
+            let [red, green, blue] = [pixel[0], pixel[1], pixel[2]].map(|value| {
+                let value = (value * divisor).max(0.0).powf(1.0 / RGBD_GAMMA);
+                (value.min(1.0) * 255.0).round() as u8
+            });
+            [red, green, blue, (divisor * 255.0).round() as u8]
+        })
+        .collect()
+}
+
+// Percent-encodes everything but unreserved characters, as glTF URIs require.
+fn encode_uri(file_name: &str) -> String {
+    let mut uri = String::new();
+    for byte in file_name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+fn object_entry<'a>(
+    object: &'a mut Map<String, Value>,
+    key: &str,
+) -> Result<&'a mut Map<String, Value>, Error> {
+    object
+        .entry(key)
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .ok_or_else(|| Error::msg(format!("`{}` isn't a JSON object", key)))
+}
+
+fn array_entry<'a>(
+    object: &'a mut Map<String, Value>,
+    key: &str,
+) -> Result<&'a mut Vec<Value>, Error> {
+    object
+        .entry(key)
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .ok_or_else(|| Error::msg(format!("`{}` isn't a JSON array", key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injecting_twice_replaces_the_light() {
+        let dir = tempfile::tempdir().unwrap();
+        let gltf_path = dir.path().join("scene.gltf");
+        fs::write(
+            &gltf_path,
+            r#"{"asset": {"version": "2.0"}, "scenes": [{"nodes": []}]}"#,
+        )
+        .unwrap();
+
+        let settings = GltfExport {
+            mode: GltfExportMode::Inject,
+            gltf_path: gltf_path.clone(),
+            ..GltfExport::default()
+        };
+        let texture = Texture {
+            width: 2,
+            height: 2,
+            levels: vec![vec![vec![1.0; 16]; 6], vec![vec![0.5; 4]; 6]],
+        };
+        for _ in 0..2 {
+            let mut staging = Staging::new();
+            export(&settings, &texture, &texture, &mut staging).unwrap();
+            staging.commit().unwrap();
+        }
+
+        let document: Value = serde_json::from_slice(&fs::read(&gltf_path).unwrap()).unwrap();
+        let lights = document["extensions"][EXTENSION_NAME]["lights"]
+            .as_array()
+            .unwrap();
+        assert_eq!(lights.len(), 1);
+        assert_eq!(document["images"].as_array().unwrap().len(), 12);
+        assert_eq!(document["extensionsUsed"], json!([EXTENSION_NAME]));
+        assert_eq!(
+            document["scenes"][0]["extensions"][EXTENSION_NAME]["light"],
+            json!(0)
+        );
+    }
+}
@@ -0,0 +1,243 @@
+// gltf-ibl-sampler-egui/src/job_file.rs
+
+use crate::generator::Job;
+use crate::os_path;
+use crate::staging::Staging;
+use anyhow::Error;
+use log::{info, warn};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub(crate) static RON_EXTENSION: &str = "ron";
+pub(crate) static JSON_EXTENSION: &str = "json";
+
+/// The version of the job format that this version of the app writes.
+///
+/// Bump it and add a migration whenever the meaning of a setting changes. New settings don't need
+/// one, since settings that are missing take their default values.
+pub(crate) const JOB_VERSION: u32 = 1;
+
+// The format didn't change when versions were added, so jobs saved before then are read as this
+// version.
+const FIRST_JOB_VERSION: u32 = 1;
+
+// `MIGRATIONS[n]` upgrades a job from version `FIRST_JOB_VERSION + n` to the version after it.
+static MIGRATIONS: [fn(&mut Job); (JOB_VERSION - FIRST_JOB_VERSION) as usize] = [];
+
+// How many recently-used job files are remembered.
+const RECENT_JOB_COUNT: usize = 10;
+
+/// The job files that were opened or saved most recently, newest first.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct RecentJobs(Vec<JobPath>);
+
+/// The path of a job file, as it's kept in the app's storage.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct JobPath(#[serde(with = "os_path")] pub(crate) PathBuf);
+
+/// Reads a job from a `.ron` or `.json` file, upgrading it from older versions.
+pub(crate) fn load(path: &Path) -> Result<Job, Error> {
+    let text = fs::read_to_string(path)?;
+    if is_json(path) {
+        Ok(upgrade(serde_json::from_str(&text)?))
+    } else {
+        decode_ron(&text)
+    }
+}
+
+/// Writes a job to a file, as JSON if the file has a `.json` extension and as RON otherwise.
+///
+/// The file is replaced atomically, so a failed save never leaves a partially-written job behind.
+pub(crate) fn save(job: &Job, path: &Path) -> Result<(), Error> {
+    let text = if is_json(path) {
+        serde_json::to_string_pretty(job)?
+    } else {
+        encode(job)?
+    };
+
+    let mut staging = Staging::new();
+    fs::write(staging.stage(path)?, text)?;
+    staging.commit()?;
+    Ok(())
+}
+
+/// Encodes a job as it's written to `.ron` files, for the clipboard.
+pub(crate) fn encode(job: &Job) -> Result<String, Error> {
+    Ok(ron::ser::to_string_pretty(job, PrettyConfig::default())?)
+}
+
+/// Decodes a job in RON, upgrading it from older versions.
+pub(crate) fn decode_ron(text: &str) -> Result<Job, Error> {
+    Ok(upgrade(ron::from_str(text)?))
+}
+
+/// Decodes a job that was pasted from the clipboard, in either RON or JSON.
+pub(crate) fn decode(text: &str) -> Result<Job, Error> {
+    decode_ron(text).or_else(|error| Ok(upgrade(serde_json::from_str(text).map_err(|_| error)?)))
+}
+
+fn upgrade(mut job: Job) -> Job {
+    migrate(&mut job);
+    job
+}
+
+/// The version of jobs that were saved before the format had versions.
+pub(crate) fn unversioned_job_version() -> u32 {
+    FIRST_JOB_VERSION
+}
+
+/// Upgrades a job that was decoded by itself from an older version of the format.
+pub(crate) fn migrate(job: &mut Job) {
+    migrate_with(job, JOB_VERSION, &MIGRATIONS);
+}
+
+fn migrate_with(job: &mut Job, current_version: u32, migrations: &[fn(&mut Job)]) {
+    if job.version > current_version {
+        warn!(
+            "The job was saved by a newer version of the app (format version {}), so some of its \
+             settings may not have been restored",
+            job.version
+        );
+    } else if job.version < current_version {
+        info!(
+            "Upgrading the job from format version {} to {}",
+            job.version, current_version
+        );
+        let first_migration = job.version.saturating_sub(FIRST_JOB_VERSION) as usize;
+        for migration in &migrations[first_migration..] {
+            migration(job);
+        }
+    }
+
+    job.version = current_version;
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(JSON_EXTENSION))
+}
+
+impl RecentJobs {
+    /// Moves the path to the front of the list, adding it if it isn't there already.
+    pub(crate) fn add(&mut self, path: &Path) {
+        let recent_job = JobPath(path.to_owned());
+        self.0.retain(|other| *other != recent_job);
+        self.0.insert(0, recent_job);
+        self.0.truncate(RECENT_JOB_COUNT);
+    }
+
+    pub(crate) fn remove(&mut self, path: &Path) {
+        self.0.retain(|recent_job| recent_job.0 != path);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.0.iter().map(|recent_job| &*recent_job.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn there_is_a_migration_for_every_older_version() {
+        assert_eq!(MIGRATIONS.len(), (JOB_VERSION - FIRST_JOB_VERSION) as usize);
+    }
+
+    #[test]
+    fn unversioned_jobs_are_read_as_the_first_version() {
+        let job: Job = ron::from_str("(max_image_size: 1024)").unwrap();
+        assert_eq!(job.version, FIRST_JOB_VERSION);
+        assert_eq!(
+            decode_ron("(max_image_size: 1024)").unwrap().version,
+            JOB_VERSION
+        );
+    }
+
+    #[test]
+    fn older_jobs_run_the_migrations_from_their_version_on() {
+        // A synthetic format at version `FIRST_JOB_VERSION + 2`, with two migrations.
+        let migrations: [fn(&mut Job); 2] =
+            [|job| job.max_image_size *= 2, |job| job.outputs.truncate(1)];
+        let current_version = FIRST_JOB_VERSION + 2;
+        let default_output_count = Job::default().outputs.len();
+        for (version, max_image_size, output_count) in [
+            (FIRST_JOB_VERSION, 2048, 1),
+            (FIRST_JOB_VERSION + 1, 1024, 1),
+            (current_version, 1024, default_output_count),
+            (current_version + 1, 1024, default_output_count),
+        ] {
+            let mut job = Job {
+                version,
+                max_image_size: 1024,
+                ..Job::default()
+            };
+            migrate_with(&mut job, current_version, &migrations);
+            assert_eq!(job.version, current_version);
+            assert_eq!(job.max_image_size, max_image_size);
+            assert_eq!(job.outputs.len(), output_count);
+        }
+    }
+
+    #[test]
+    fn current_jobs_are_left_alone() {
+        let job = Job::default();
+        let mut migrated_job = job.clone();
+        migrate(&mut migrated_job);
+        assert!(migrated_job == job);
+    }
+
+    #[test]
+    fn newer_jobs_are_not_migrated() {
+        let mut job = Job {
+            version: JOB_VERSION + 1,
+            ..Job::default()
+        };
+        migrate(&mut job);
+        assert_eq!(job.version, JOB_VERSION);
+    }
+
+    #[test]
+    fn jobs_round_trip_through_ron_and_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut job = Job {
+            input_path: PathBuf::from("studio.hdr"),
+            max_image_size: 2048,
+            ..Job::default()
+        };
+        job.outputs[1].enabled = false;
+
+        for extension in [RON_EXTENSION, JSON_EXTENSION] {
+            let path = dir.path().join("job").with_extension(extension);
+            save(&job, &path).unwrap();
+            assert!(load(&path).unwrap() == job);
+        }
+        let json = fs::read_to_string(dir.path().join("job.json")).unwrap();
+        assert!(decode(&json).unwrap() == job);
+    }
+
+    #[test]
+    fn recent_jobs_are_newest_first_without_duplicates() {
+        let mut recent_jobs = RecentJobs::default();
+        for index in 0..=RECENT_JOB_COUNT {
+            recent_jobs.add(Path::new(&format!("{}.ron", index)));
+        }
+        recent_jobs.add(Path::new("5.ron"));
+
+        let paths: Vec<_> = recent_jobs.paths().collect();
+        assert_eq!(paths.len(), RECENT_JOB_COUNT);
+        assert_eq!(paths[0], Path::new("5.ron"));
+        assert_eq!(paths[1], Path::new("10.ron"));
+        assert!(!paths.contains(&Path::new("0.ron")));
+    }
+}
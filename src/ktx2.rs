@@ -0,0 +1,460 @@
+// gltf-ibl-sampler-egui/src/ktx2.rs
+
+use anyhow::Error;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+static KTX2_IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+static KTX2_WRITER: &str = concat!("gltf-ibl-sampler-egui ", env!("CARGO_PKG_VERSION"));
+
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+pub(crate) const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+pub(crate) const VK_FORMAT_R16G16B16A16_SFLOAT: u32 = 97;
+pub(crate) const VK_FORMAT_R32G32B32A32_SFLOAT: u32 = 109;
+pub(crate) const VK_FORMAT_E5B9G9R9_UFLOAT_PACK32: u32 = 123;
+pub(crate) const VK_FORMAT_ASTC_4x4_SFLOAT_BLOCK: u32 = 1000066000;
+pub(crate) const VK_FORMAT_ASTC_6x6_SFLOAT_BLOCK: u32 = 1000066004;
+
+// Data format descriptor constants, from the Khronos Data Format Specification.
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_MODEL_ASTC: u8 = 162;
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+const KHR_DF_SAMPLE_DATATYPE_EXPONENT: u8 = 0x20;
+const KHR_DF_SAMPLE_DATATYPE_SIGNED: u8 = 0x40;
+const KHR_DF_SAMPLE_DATATYPE_FLOAT: u8 = 0x80;
+const KHR_DF_CHANNEL_RGBSDA_ALPHA: u8 = 15;
+const FLOAT_MINUS_ONE: u32 = 0xbf80_0000;
+const FLOAT_ONE: u32 = 0x3f80_0000;
+
+/// An uncompressed (in the supercompression sense) KTX2 texture, either a 2D image or a cubemap.
+pub(crate) struct Ktx2Texture {
+    pub(crate) vk_format: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) face_count: u32,
+    /// The data for each mip level, starting with the largest. Each level contains every face of
+    /// that level, one after another, in the usual +X, -X, +Y, -Y, +Z, -Z order.
+    pub(crate) levels: Vec<Vec<u8>>,
+}
+
+struct FormatInfo {
+    type_size: u32,
+    block_width: u32,
+    block_height: u32,
+    bytes_per_block: u32,
+    color_model: u8,
+    // Bit offset, bit length, channel type, lower bound, upper bound.
+    samples: &'static [(u32, u32, u8, u32, u32)],
+}
+
+impl Ktx2Texture {
+    pub(crate) fn read(path: &Path) -> Result<Ktx2Texture, Error> {
+        let mut bytes = vec![];
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        if bytes.len() < HEADER_SIZE || bytes[0..12] != KTX2_IDENTIFIER {
+            return Err(Error::msg("Not a KTX2 file"));
+        }
+
+        let vk_format = read_u32(&bytes, 12)?;
+        let width = read_u32(&bytes, 20)?;
+        let height = read_u32(&bytes, 24)?.max(1);
+        let face_count = read_u32(&bytes, 36)?;
+        let level_count = read_u32(&bytes, 40)?.max(1);
+        if read_u32(&bytes, 44)? != 0 {
+            return Err(Error::msg("Supercompressed KTX2 files aren't supported"));
+        }
+
+        let mut levels = vec![];
+        for level in 0..(level_count as usize) {
+            let entry_offset = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            let byte_offset = usize::try_from(read_u64(&bytes, entry_offset)?)?;
+            let byte_length = usize::try_from(read_u64(&bytes, entry_offset + 8)?)?;
+            let level_data = byte_offset
+                .checked_add(byte_length)
+                .and_then(|end| bytes.get(byte_offset..end))
+                .ok_or_else(|| Error::msg("KTX2 mip level out of bounds"))?;
+            levels.push(level_data.to_vec());
+        }
+
+        Ok(Ktx2Texture {
+            vk_format,
+            width,
+            height,
+            face_count,
+            levels,
+        })
+    }
+
+    /// Reads just the width and mip level count of a KTX2 file, without loading its data.
+    pub(crate) fn read_dimensions(path: &Path) -> Result<(u32, u32), Error> {
+        let mut header = [0; HEADER_SIZE];
+        File::open(path)?.read_exact(&mut header)?;
+        if header[0..12] != KTX2_IDENTIFIER {
+            return Err(Error::msg("Not a KTX2 file"));
+        }
+        Ok((read_u32(&header, 20)?, read_u32(&header, 40)?.max(1)))
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<(), Error> {
+        let format_info = format_info(self.vk_format)
+            .ok_or_else(|| Error::msg("Unsupported KTX2 output format"))?;
+
+        let dfd = data_format_descriptor(&format_info);
+        let kvd = key_value_data(&[("KTXwriter", KTX2_WRITER)]);
+
+        let dfd_offset = HEADER_SIZE + self.levels.len() * LEVEL_INDEX_ENTRY_SIZE;
+        let kvd_offset = dfd_offset + dfd.len();
+
+        // Mip levels must be aligned to the least common multiple of the block size and 4, and
+        // they're stored from smallest to largest.
+        let alignment = lcm(format_info.bytes_per_block as usize, 4);
+        let mut level_offsets = vec![0; self.levels.len()];
+        let mut offset = kvd_offset + kvd.len();
+        for (level_index, level) in self.levels.iter().enumerate().rev() {
+            offset = offset.next_multiple_of(alignment);
+            level_offsets[level_index] = offset;
+            offset += level.len();
+        }
+
+        let mut header = vec![];
+        header.extend_from_slice(&KTX2_IDENTIFIER);
+        for value in [
+            self.vk_format,
+            format_info.type_size,
+            self.width,
+            self.height,
+            /*pixelDepth=*/ 0,
+            /*layerCount=*/ 0,
+            self.face_count,
+            self.levels.len() as u32,
+            /*supercompressionScheme=*/ 0,
+            dfd_offset as u32,
+            dfd.len() as u32,
+            kvd_offset as u32,
+            kvd.len() as u32,
+        ] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        // No supercompression global data.
+        header.extend_from_slice(&0u64.to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes());
+
+        for (level, level_offset) in self.levels.iter().zip(level_offsets.iter()) {
+            header.extend_from_slice(&(*level_offset as u64).to_le_bytes());
+            header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+            header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+        }
+
+        header.extend_from_slice(&dfd);
+        header.extend_from_slice(&kvd);
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&header)?;
+        let mut position = header.len();
+        for (level_index, level) in self.levels.iter().enumerate().rev() {
+            let padding = level_offsets[level_index] - position;
+            writer.write_all(&vec![0; padding])?;
+            writer.write_all(level)?;
+            position = level_offsets[level_index] + level.len();
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns the width and height of the given mip level.
+    pub(crate) fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+}
+
+fn format_info(vk_format: u32) -> Option<FormatInfo> {
+    const FLOAT: u8 = KHR_DF_SAMPLE_DATATYPE_FLOAT | KHR_DF_SAMPLE_DATATYPE_SIGNED;
+
+    match vk_format {
+        VK_FORMAT_R8G8B8A8_UNORM => Some(FormatInfo {
+            type_size: 1,
+            block_width: 1,
+            block_height: 1,
+            bytes_per_block: 4,
+            color_model: KHR_DF_MODEL_RGBSDA,
+            samples: &[
+                (0, 8, 0, 0, 255),
+                (8, 8, 1, 0, 255),
+                (16, 8, 2, 0, 255),
+                (24, 8, KHR_DF_CHANNEL_RGBSDA_ALPHA, 0, 255),
+            ],
+        }),
+        VK_FORMAT_R16G16B16A16_SFLOAT => Some(FormatInfo {
+            type_size: 2,
+            block_width: 1,
+            block_height: 1,
+            bytes_per_block: 8,
+            color_model: KHR_DF_MODEL_RGBSDA,
+            samples: &[
+                (0, 16, FLOAT, FLOAT_MINUS_ONE, FLOAT_ONE),
+                (16, 16, FLOAT | 1, FLOAT_MINUS_ONE, FLOAT_ONE),
+                (32, 16, FLOAT | 2, FLOAT_MINUS_ONE, FLOAT_ONE),
+                (
+                    48,
+                    16,
+                    FLOAT | KHR_DF_CHANNEL_RGBSDA_ALPHA,
+                    FLOAT_MINUS_ONE,
+                    FLOAT_ONE,
+                ),
+            ],
+        }),
+        VK_FORMAT_R32G32B32A32_SFLOAT => Some(FormatInfo {
+            type_size: 4,
+            block_width: 1,
+            block_height: 1,
+            bytes_per_block: 16,
+            color_model: KHR_DF_MODEL_RGBSDA,
+            samples: &[
+                (0, 32, FLOAT, FLOAT_MINUS_ONE, FLOAT_ONE),
+                (32, 32, FLOAT | 1, FLOAT_MINUS_ONE, FLOAT_ONE),
+                (64, 32, FLOAT | 2, FLOAT_MINUS_ONE, FLOAT_ONE),
+                (
+                    96,
+                    32,
+                    FLOAT | KHR_DF_CHANNEL_RGBSDA_ALPHA,
+                    FLOAT_MINUS_ONE,
+                    FLOAT_ONE,
+                ),
+            ],
+        }),
+        // Each channel is described by a mantissa and the shared exponent, as in the Khronos Data
+        // Format Specification's example.
+        VK_FORMAT_E5B9G9R9_UFLOAT_PACK32 => Some(FormatInfo {
+            type_size: 4,
+            block_width: 1,
+            block_height: 1,
+            bytes_per_block: 4,
+            color_model: KHR_DF_MODEL_RGBSDA,
+            samples: &[
+                (0, 9, 0, 0, 8448),
+                (27, 5, KHR_DF_SAMPLE_DATATYPE_EXPONENT, 15, 31),
+                (9, 9, 1, 0, 8448),
+                (27, 5, KHR_DF_SAMPLE_DATATYPE_EXPONENT | 1, 15, 31),
+                (18, 9, 2, 0, 8448),
+                (27, 5, KHR_DF_SAMPLE_DATATYPE_EXPONENT | 2, 15, 31),
+            ],
+        }),
+        VK_FORMAT_ASTC_4x4_SFLOAT_BLOCK | VK_FORMAT_ASTC_6x6_SFLOAT_BLOCK => {
+            let block_size = if vk_format == VK_FORMAT_ASTC_4x4_SFLOAT_BLOCK {
+                4
+            } else {
+                6
+            };
+            Some(FormatInfo {
+                type_size: 1,
+                block_width: block_size,
+                block_height: block_size,
+                bytes_per_block: 16,
+                color_model: KHR_DF_MODEL_ASTC,
+                samples: &[(0, 128, FLOAT, FLOAT_MINUS_ONE, FLOAT_ONE)],
+            })
+        }
+        _ => None,
+    }
+}
+
+fn data_format_descriptor(format_info: &FormatInfo) -> Vec<u8> {
+    let block_size = 24 + 16 * format_info.samples.len() as u32;
+
+    let mut words = vec![
+        // Total size, including this field.
+        block_size + 4,
+        // Vendor ID and descriptor type are both zero.
+        0,
+        // Version 2 of the basic descriptor block.
+        2 | (block_size << 16),
+        u32::from_le_bytes([
+            format_info.color_model,
+            KHR_DF_PRIMARIES_BT709,
+            KHR_DF_TRANSFER_LINEAR,
+            /*flags=*/ 0,
+        ]),
+        u32::from_le_bytes([
+            (format_info.block_width - 1) as u8,
+            (format_info.block_height - 1) as u8,
+            0,
+            0,
+        ]),
+        format_info.bytes_per_block,
+        0,
+    ];
+
+    for &(bit_offset, bit_length, channel_type, lower, upper) in format_info.samples {
+        words.push(bit_offset | (bit_length - 1) << 16 | (channel_type as u32) << 24);
+        words.push(0);
+        words.push(lower);
+        words.push(upper);
+    }
+
+    words.into_iter().flat_map(u32::to_le_bytes).collect()
+}
+
+fn key_value_data(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut data = vec![];
+    for (key, value) in entries {
+        let length = key.len() + value.len() + 2;
+        data.extend_from_slice(&(length as u32).to_le_bytes());
+        data.extend_from_slice(key.as_bytes());
+        data.push(0);
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+    data
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    bytes
+        .get(offset..(offset + 4))
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| Error::msg("Truncated KTX2 file"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Error> {
+    bytes
+        .get(offset..(offset + 8))
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| Error::msg("Truncated KTX2 file"))
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Returns a cubemap with three mip levels, whose texels hold the index of their face.
+    fn cubemap(vk_format: u32, bytes_per_texel: usize) -> Ktx2Texture {
+        Ktx2Texture {
+            vk_format,
+            width: 4,
+            height: 4,
+            face_count: 6,
+            levels: [4, 2, 1]
+                .into_iter()
+                .map(|size| {
+                    (0..6u8)
+                        .flat_map(|face| vec![face; size * size * bytes_per_texel])
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        read_u32(bytes, offset).unwrap()
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> usize {
+        read_u64(bytes, offset).unwrap() as usize
+    }
+
+    #[test]
+    fn header_describes_the_texture() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cubemap.ktx2");
+        cubemap(VK_FORMAT_R16G16B16A16_SFLOAT, 8)
+            .write(&path)
+            .unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        assert_eq!(bytes[0..12], KTX2_IDENTIFIER);
+        assert_eq!(u32_at(&bytes, 12), VK_FORMAT_R16G16B16A16_SFLOAT);
+        // typeSize, width, height, pixelDepth, layerCount, faceCount, levelCount and
+        // supercompressionScheme.
+        let fields: Vec<_> = (16..48)
+            .step_by(4)
+            .map(|offset| u32_at(&bytes, offset))
+            .collect();
+        assert_eq!(fields, [2, 4, 4, 0, 0, 6, 3, 0]);
+
+        // The data format descriptor follows the level index, and the key/value data follows it.
+        let (dfd_offset, dfd_length) = (u32_at(&bytes, 48) as usize, u32_at(&bytes, 52) as usize);
+        let (kvd_offset, kvd_length) = (u32_at(&bytes, 56) as usize, u32_at(&bytes, 60) as usize);
+        assert_eq!(dfd_offset, HEADER_SIZE + 3 * LEVEL_INDEX_ENTRY_SIZE);
+        assert_eq!(u32_at(&bytes, dfd_offset) as usize, dfd_length);
+        assert_eq!(dfd_length, 4 + 24 + 4 * 16);
+        assert_eq!(kvd_offset, dfd_offset + dfd_length);
+        let kvd = &bytes[kvd_offset..(kvd_offset + kvd_length)];
+        assert!(kvd.windows(10).any(|window| window == b"KTXwriter\0"));
+
+        // Levels are stored from smallest to largest, each aligned to 8 bytes.
+        let mut end = kvd_offset + kvd_length;
+        for level in (0..3).rev() {
+            let entry_offset = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            let offset = u64_at(&bytes, entry_offset);
+            let length = 6 * (4 >> level) * (4 >> level) * 8;
+            assert_eq!(offset % 8, 0);
+            assert!(offset >= end);
+            assert_eq!(u64_at(&bytes, entry_offset + 8), length);
+            assert_eq!(u64_at(&bytes, entry_offset + 16), length);
+            end = offset + length;
+        }
+        assert_eq!(end, bytes.len());
+    }
+
+    #[test]
+    fn textures_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cubemap.ktx2");
+        let texture = cubemap(VK_FORMAT_R32G32B32A32_SFLOAT, 16);
+        texture.write(&path).unwrap();
+
+        let read_texture = Ktx2Texture::read(&path).unwrap();
+        assert_eq!(read_texture.vk_format, texture.vk_format);
+        assert_eq!(
+            (read_texture.width, read_texture.height),
+            (texture.width, texture.height)
+        );
+        assert_eq!(read_texture.face_count, 6);
+        assert_eq!(read_texture.levels, texture.levels);
+        assert_eq!(Ktx2Texture::read_dimensions(&path).unwrap(), (4, 3));
+    }
+
+    #[test]
+    fn astc_textures_are_described_as_blocks() {
+        let dfd = data_format_descriptor(&format_info(VK_FORMAT_ASTC_6x6_SFLOAT_BLOCK).unwrap());
+        assert_eq!(dfd.len(), 4 + 24 + 16);
+        assert_eq!(dfd[12], KHR_DF_MODEL_ASTC);
+        // The texel block dimensions, minus one.
+        assert_eq!(dfd[16..18], [5, 5]);
+        assert_eq!(u32_at(&dfd, 20), 16);
+        // A single 128-bit sample.
+        let channel_type = u32::from(KHR_DF_SAMPLE_DATATYPE_FLOAT | KHR_DF_SAMPLE_DATATYPE_SIGNED);
+        assert_eq!(u32_at(&dfd, 28), 127 << 16 | channel_type << 24);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cubemap.ktx2");
+        fs::write(&path, [0; HEADER_SIZE]).unwrap();
+        assert!(Ktx2Texture::read(&path).is_err());
+        assert!(Ktx2Texture::read_dimensions(&path).is_err());
+
+        cubemap(VK_FORMAT_R8G8B8A8_UNORM, 4).write(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..(bytes.len() - 1)]).unwrap();
+        assert!(Ktx2Texture::read(&path).is_err());
+    }
+}
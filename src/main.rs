@@ -2,22 +2,32 @@
 
 #![allow(non_upper_case_globals)]
 
-use crate::generator::{FilterSettings, Job, OutputProgress};
+use crate::generator::{BevyVersion, FilterSettings, GltfExportMode, Job, OutputProgress};
+use anyhow::Error;
 use eframe::{self, icon_data, App, CreationContext, Frame as EFrame, NativeOptions, Storage};
 use egui::load::SizedTexture;
 use egui::text::LayoutJob;
+use egui::util::undoer::Undoer;
 use egui::{
-    Align, Button, CentralPanel, CollapsingHeader, Color32, ColorImage, ComboBox, Context,
-    FontData, FontDefinitions, FontFamily, FontId, Grid, Id, ImageSource, Layout, ProgressBar,
-    RichText, ScrollArea, TextEdit, TextFormat, TextureHandle, TextureOptions, TopBottomPanel, Ui,
-    Vec2, ViewportBuilder, Window,
+    menu, Align, Button, CentralPanel, CollapsingHeader, Color32, ColorImage, ComboBox, Context,
+    FontData, FontDefinitions, FontFamily, FontId, Grid, Id, ImageSource, Key, KeyboardShortcut,
+    Layout, Modifiers, ProgressBar, RichText, ScrollArea, TextEdit, TextFormat, TextureHandle,
+    TextureOptions, TopBottomPanel, Ui, Vec2, ViewportBuilder, ViewportCommand, Window,
+};
+use filename::TemplateContext;
+use generator::{
+    ContainerFormat, Distribution, InputReencodingStatus, Output, OutputState, OutputStatus,
+    Projection, SamplerProgress, TargetFormat,
 };
-use generator::{Distribution, InputReencodingStatus, Output, TargetFormat};
 use image::imageops::FilterType;
+use job_file::{JobPath, RecentJobs};
 use log::{warn, Level, LevelFilter, Log, Metadata, Record};
+use presets::Preset;
+use queue::{JobQueue, QueuedJobState};
 use rfd::{FileDialog, MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
 use rust_i18n::t;
 use std::collections::HashSet;
+use std::env;
 use std::ffi::OsStr;
 use std::fmt::{Display, Write};
 use std::iter;
@@ -25,15 +35,39 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
+use validation::{JobField, OutputField, ValidationIssue};
+use worker::WorkerHandle;
 
 rust_i18n::i18n!("locales");
 
-#[allow(non_camel_case_types, non_upper_case_globals)]
+#[allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
 mod bindgen {
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
 
+mod astc;
+mod bevy;
+mod capture;
+mod cleanup;
+mod dds;
+mod filename;
 mod generator;
+mod gltf;
+mod job_file;
+mod ktx2;
+mod manifest;
+mod openexr;
+mod os_path;
+mod presets;
+mod projection;
+mod queue;
+mod radiance;
+mod reveal;
+mod staging;
+mod texture;
+mod validation;
+mod worker;
 
 // Internally, the image preview is stored at this resolution to save on VRAM.
 const INTERNALIMAGE_PREVIEW_HEIGHT: u32 = 480;
@@ -42,16 +76,52 @@ const DEFAULT_IMAGE_PREVIEW_HEIGHT: f32 = 128.0;
 
 static INITIAL_WINDOW_SIZE: Vec2 = Vec2::new(480.0, 640.0);
 
+static OPEN_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::O);
+static SAVE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::S);
+static SAVE_AS_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::S);
+static UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+static REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+
 static ICON_PNG_DATA: &[u8] = include_bytes!("../Icon.png");
 static FONT_DATA: &[u8] = include_bytes!("../PublicSans-Regular.ttf");
 
 struct IblSamplerApp {
     job: Job,
+    // The file that the job was last opened from or saved to, if any.
+    job_path: Option<PathBuf>,
+    // The job as it was when it was last opened or saved, to tell whether it has unsaved changes.
+    saved_job: Job,
+    recent_jobs: RecentJobs,
+    title: String,
+    // Edits to the job that can be undone.
+    history: Undoer<Job>,
     input_preview: Arc<Mutex<InputPreview>>,
     output_progress: Arc<Mutex<OutputProgress>>,
+    // Jobs waiting to run in the background, with the progress of the one that's running.
+    queue: JobQueue,
+    queue_progress: Arc<Mutex<OutputProgress>>,
+    queue_worker: WorkerHandle,
+    queue_window_open: bool,
     just_loaded: bool,
     top_panel_resized_by_user: bool,
     log_window_open: bool,
+    user_presets: Vec<Preset>,
+    new_preset_name: String,
+    worker: WorkerHandle,
+    validation_issues: Vec<ValidationIssue>,
+    // The job that `validation_issues` were found in, or `None` if it has to be checked again.
+    validated_job: Option<Job>,
+    window_focused: bool,
+}
+
+// Changes to the queue that the user asked for, applied after the queue has been shown.
+enum QueueAction {
+    Move(usize, usize),
+    Retry(usize),
+    Edit(usize),
+    Remove(usize),
 }
 
 struct InputPreview {
@@ -79,9 +149,20 @@ static LOG_BUFFER: LogBuffer = LogBuffer(Mutex::new(LogBufferImpl {
 }));
 
 fn main() {
+    // The sampler opens files through the C runtime.
+    os_path::use_utf8_in_c_runtime();
+
+    if env::args_os().any(|argument| argument == worker::WORKER_ARGUMENT) {
+        worker::worker_main();
+        return;
+    }
+
     drop(log::set_logger(&LOG_BUFFER));
     log::set_max_level(LevelFilter::Info);
 
+    // Runs that crashed may have left large files behind.
+    thread::spawn(cleanup::remove_stale_temporary_files);
+
     let mut viewport_builder = ViewportBuilder::default()
         .with_inner_size(INITIAL_WINDOW_SIZE)
         .with_app_id("GLTFIBLSampler".to_owned());
@@ -108,7 +189,56 @@ impl IblSamplerApp {
         let job = ctx
             .storage
             .and_then(|storage| storage.get_string("job"))
-            .and_then(|encoded_job| ron::from_str(&encoded_job).ok())
+            .and_then(|encoded_job| match job_file::decode_ron(&encoded_job) {
+                Ok(job) => Some(job),
+                Err(error) => {
+                    warn!(
+                        "The saved job couldn't be restored, so the default job was loaded \
+                         instead: {}",
+                        error
+                    );
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let user_presets = ctx
+            .storage
+            .and_then(|storage| storage.get_string("presets"))
+            .and_then(|encoded_presets| match ron::from_str(&encoded_presets) {
+                Ok(presets) => Some(presets),
+                Err(error) => {
+                    warn!("The saved presets couldn't be restored: {}", error);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let recent_jobs: RecentJobs = ctx
+            .storage
+            .and_then(|storage| storage.get_string("recent_jobs"))
+            .and_then(|encoded_recent_jobs| ron::from_str(&encoded_recent_jobs).ok())
+            .unwrap_or_default();
+        let queue = ctx
+            .storage
+            .and_then(|storage| storage.get_string("queue"))
+            .and_then(|encoded_queue| match JobQueue::restore(&encoded_queue) {
+                Ok(queue) => Some(queue),
+                Err(error) => {
+                    warn!("The saved queue couldn't be restored: {}", error);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        // The job in storage came from the most recent job file if it has unsaved changes.
+        let job_path = ctx
+            .storage
+            .and_then(|storage| storage.get_string("job_path"))
+            .and_then(|encoded_job_path| ron::from_str::<Option<JobPath>>(&encoded_job_path).ok())
+            .flatten()
+            .map(|job_path| job_path.0);
+        let saved_job = job_path
+            .as_deref()
+            .and_then(|job_path| job_file::load(job_path).ok())
             .unwrap_or_default();
 
         // Load a custom font.
@@ -128,14 +258,29 @@ impl IblSamplerApp {
 
         Box::new(IblSamplerApp {
             job,
+            job_path,
+            saved_job,
+            recent_jobs,
+            title: String::new(),
+            history: Undoer::default(),
             input_preview: Arc::new(Mutex::new(InputPreview {
                 payload: InputPreviewPayload::NoneSelected,
                 epoch: 0,
             })),
             output_progress: Arc::new(Mutex::new(OutputProgress::NotStartedYet)),
+            queue,
+            queue_progress: Arc::new(Mutex::new(OutputProgress::NotStartedYet)),
+            queue_worker: WorkerHandle::default(),
+            queue_window_open: false,
             just_loaded: true,
             top_panel_resized_by_user: false,
             log_window_open: false,
+            user_presets,
+            new_preset_name: String::new(),
+            worker: WorkerHandle::default(),
+            validation_issues: vec![],
+            validated_job: None,
+            window_focused: true,
         })
     }
 }
@@ -153,9 +298,17 @@ impl App for IblSamplerApp {
 
         let mut files_changed = false;
 
+        self.shortcuts(ctx);
+        self.run_queue(ctx);
+        self.validate_job(ctx);
+
         TopBottomPanel::top("IblTopPanel")
             .resizable(true)
             .show(ctx, |ui| {
+                menu::bar(ui, |ui| {
+                    ui.menu_button(&t!("menu.file"), |ui| self.file_menu_ui(ui));
+                    ui.menu_button(&t!("menu.edit"), |ui| self.edit_menu_ui(ui));
+                });
                 ui.vertical_centered(|ui| files_changed = self.input_ui(ui) || files_changed);
             });
 
@@ -181,6 +334,7 @@ impl App for IblSamplerApp {
                 .first()
                 .and_then(|path| path.path.clone())
             {
+                self.checkpoint();
                 self.set_input_path(ctx, new_path);
                 files_changed = true;
             }
@@ -199,12 +353,38 @@ impl App for IblSamplerApp {
             .collapsible(false)
             .show(ctx, |ui| self.log_window_ui(ui));
         self.log_window_open = log_window_open;
+
+        // Queue window
+        let mut queue_window_open = self.queue_window_open;
+        Window::new(t!("queue.window.title"))
+            .open(&mut queue_window_open)
+            .default_open(false)
+            .scroll2([false, true])
+            .collapsible(false)
+            .show(ctx, |ui| self.queue_window_ui(ui));
+        self.queue_window_open = queue_window_open;
+
+        self.history
+            .feed_state(ctx.input(|input| input.time), &self.job);
+        self.update_title(ctx);
     }
 
     fn save(&mut self, storage: &mut dyn Storage) {
         if let Ok(job) = ron::to_string(&self.job) {
             storage.set_string("job", job)
         }
+        if let Ok(job_path) = ron::to_string(&self.job_path.clone().map(JobPath)) {
+            storage.set_string("job_path", job_path)
+        }
+        if let Ok(recent_jobs) = ron::to_string(&self.recent_jobs) {
+            storage.set_string("recent_jobs", recent_jobs)
+        }
+        if let Ok(presets) = ron::to_string(&self.user_presets) {
+            storage.set_string("presets", presets)
+        }
+        if let Ok(queue) = ron::to_string(&self.queue) {
+            storage.set_string("queue", queue)
+        }
     }
 }
 
@@ -213,12 +393,22 @@ impl IblSamplerApp {
     fn outputs_ui(&mut self, ui: &mut Ui) -> bool {
         let mut files_changed = false;
         let mut outputs_to_delete = vec![];
+        let mut output_to_duplicate = None;
+        let mut output_to_move = None;
 
         ScrollArea::vertical()
             .auto_shrink([false, true])
             .show(ui, |ui| {
+                ui.group(|ui| {
+                    CollapsingHeader::new(t!("preset.header"))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            files_changed = self.presets_ui(ui) || files_changed
+                        });
+                });
+
                 for output_index in 0..self.job.outputs.len() {
-                    ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                    let response = ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
                         ui.group(|ui| {
                             if self.job.outputs.len() > 1
                                 && ui
@@ -229,17 +419,101 @@ impl IblSamplerApp {
                                 outputs_to_delete.push(output_index);
                             }
 
-                            CollapsingHeader::new(t!("output.header", index = (output_index + 1)))
+                            if ui
+                                .button("⧉")
+                                .on_hover_text(layout_text_with_code(&t!("help.output.duplicate")))
+                                .clicked()
+                            {
+                                output_to_duplicate = Some(output_index);
+                            }
+
+                            let mut enabled = self.job.outputs[output_index].enabled;
+                            if ui
+                                .checkbox(&mut enabled, "")
+                                .on_hover_text(layout_text_with_code(&t!("help.output.enabled")))
+                                .changed()
+                            {
+                                self.checkpoint();
+                                self.job.outputs[output_index].enabled = enabled;
+                            }
+
+                            if self.job.outputs.len() > 1 {
+                                ui.dnd_drag_source(
+                                    Id::new("IblOutputDragHandle").with(output_index),
+                                    output_index,
+                                    |ui| ui.label("☰"),
+                                )
+                                .response
+                                .on_hover_text(layout_text_with_code(&t!("help.output.move")));
+                            }
+
+                            let mut header = t!("output.header", index = (output_index + 1));
+                            if !enabled {
+                                header = t!("output.header.disabled", header = header);
+                            }
+                            CollapsingHeader::new(header)
+                                .id_source(Id::new("IblOutputHeader").with(output_index))
                                 .default_open(true)
                                 .show(ui, |ui| {
-                                    files_changed =
-                                        self.output_ui(ui, output_index) || files_changed
+                                    ui.add_enabled_ui(enabled, |ui| {
+                                        files_changed =
+                                            self.output_ui(ui, output_index) || files_changed
+                                    });
                                 });
                         });
                     });
+
+                    // Outputs that are dropped onto another output take its place.
+                    let response = response.response;
+                    if response.dnd_hover_payload::<usize>().is_some() {
+                        ui.painter().rect_stroke(
+                            response.rect,
+                            ui.visuals().widgets.active.rounding,
+                            ui.visuals().widgets.active.fg_stroke,
+                        );
+                    }
+                    if let Some(moved_output_index) = response.dnd_release_payload::<usize>() {
+                        output_to_move = Some((*moved_output_index, output_index));
+                    }
                 }
+
+                ui.group(|ui| {
+                    CollapsingHeader::new(t!("gltf.export.header"))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            files_changed = self.gltf_export_ui(ui) || files_changed
+                        });
+                });
+
+                ui.group(|ui| {
+                    CollapsingHeader::new(t!("bevy.header"))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            files_changed = self.bevy_bundle_ui(ui) || files_changed
+                        });
+                });
             });
 
+        if let Some(output_index) = output_to_duplicate {
+            self.checkpoint();
+            let mut output = self.job.outputs[output_index].clone();
+            // The copy would overwrite the original's files otherwise.
+            output.use_automatic_paths();
+            self.job.outputs.insert(output_index + 1, output);
+            files_changed = true;
+        }
+
+        if let Some((from_index, to_index)) = output_to_move {
+            if from_index != to_index {
+                self.checkpoint();
+                let output = self.job.outputs.remove(from_index);
+                self.job.outputs.insert(to_index, output);
+            }
+        }
+
+        if !outputs_to_delete.is_empty() {
+            self.checkpoint();
+        }
         outputs_to_delete.sort();
         for output_to_delete in outputs_to_delete.into_iter().rev() {
             self.job.outputs.remove(output_to_delete);
@@ -266,6 +540,7 @@ impl IblSamplerApp {
                         .add_filter(t!("input.file.type"), &["hdr", "exr"])
                         .pick_file()
                     {
+                        self.checkpoint();
                         self.set_input_path(ui.ctx(), path);
                         file_changed = true;
                     }
@@ -283,6 +558,7 @@ impl IblSamplerApp {
             });
 
             ui.end_row();
+            field_issues_ui(ui, &self.validation_issues, JobField::InputPath);
 
             // Maximum size
             output_numeric_value_ui(
@@ -291,6 +567,34 @@ impl IblSamplerApp {
                 &t!("input.max.image.size"),
                 Some(&t!("help.input.max.image.size")),
             );
+            field_issues_ui(ui, &self.validation_issues, JobField::MaxImageSize);
+
+            // Transactional
+            ui.label(&t!("job.transactional"));
+            ui.checkbox(
+                &mut self.job.transactional,
+                t!("job.transactional.checkbox"),
+            )
+            .on_hover_text(layout_text_with_code(&t!("help.job.transactional")));
+            ui.end_row();
+
+            // Worker process
+            ui.label(&t!("job.run.in.worker"));
+            ui.checkbox(
+                &mut self.job.run_in_worker,
+                t!("job.run.in.worker.checkbox"),
+            )
+            .on_hover_text(layout_text_with_code(&t!("help.job.run.in.worker")));
+            ui.end_row();
+
+            // Force rebuild
+            ui.label(t!("job.force.rebuild"));
+            ui.checkbox(
+                &mut self.job.force_rebuild,
+                t!("job.force.rebuild.checkbox"),
+            )
+            .on_hover_text(layout_text_with_code(&t!("help.job.force.rebuild")));
+            ui.end_row();
         });
 
         if let Ok(maybe_texture) = self.input_preview.lock() {
@@ -329,6 +633,7 @@ impl IblSamplerApp {
 
     fn bottom_panel_ui(&mut self, ui: &mut Ui) {
         ui.horizontal(|ui| self.output_progress_ui(ui));
+        self.output_results_ui(ui);
 
         ui.horizontal(|ui| {
             if ui
@@ -336,23 +641,53 @@ impl IblSamplerApp {
                 .on_hover_text(layout_text_with_code(&t!("help.output.add")))
                 .clicked()
             {
+                self.checkpoint();
                 let new_output = Output::default_for_index(self.job.outputs.len());
                 self.job.outputs.push(new_output);
             }
 
             ui.with_layout(Layout::right_to_left(Align::Max), |ui| {
                 // Generate button
-                let disabled = matches!(
-                    *self.output_progress.lock().unwrap(),
-                    OutputProgress::InProgress { .. }
-                );
+                let disabled = self.job_in_progress();
+                let invalid = !self.validation_issues.is_empty();
                 if ui
-                    .add_enabled(!disabled, Button::new(t!("button.generate")))
+                    .add_enabled(!disabled && !invalid, Button::new(t!("button.generate")))
                     .on_hover_text(t!("help.button.generate"))
+                    .on_disabled_hover_text(if invalid {
+                        t!("help.button.generate.invalid")
+                    } else {
+                        t!("help.button.generate")
+                    })
+                    .clicked()
+                    && self.check_for_overwrite_and_prompt_user()
+                {
+                    self.worker = generator::generate(
+                        ui.ctx(),
+                        self.job.clone(),
+                        self.output_progress.clone(),
+                    );
+                }
+
+                // Add to Queue button
+                if ui
+                    .add_enabled(!invalid, Button::new(t!("button.add.to.queue")))
+                    .on_hover_text(t!("help.button.add.to.queue"))
+                    .on_disabled_hover_text(t!("help.button.generate.invalid"))
                     .clicked()
                     && self.check_for_overwrite_and_prompt_user()
                 {
-                    generator::generate(ui.ctx(), self.job.clone(), self.output_progress.clone());
+                    self.queue.add(self.job.clone());
+                    self.queue_window_open = true;
+                }
+
+                // Stop button
+                if self.worker.is_running()
+                    && ui
+                        .button(&t!("button.stop"))
+                        .on_hover_text(t!("help.button.stop"))
+                        .clicked()
+                {
+                    self.worker.stop();
                 }
 
                 // Reset button
@@ -361,9 +696,19 @@ impl IblSamplerApp {
                     .on_hover_text(t!("help.button.reset"))
                     .clicked()
                 {
+                    self.checkpoint();
                     self.job = Job::default();
                 }
 
+                // Show Queue button
+                if ui
+                    .button(t!("button.show.queue", count = self.queue.jobs.len()))
+                    .on_hover_text(t!("help.button.show.queue"))
+                    .clicked()
+                {
+                    self.queue_window_open = true;
+                }
+
                 // Show Log button
                 if ui
                     .button(&t!("button.show.log"))
@@ -376,7 +721,6 @@ impl IblSamplerApp {
         });
     }
 
-    #[allow(clippy::eq_op)]
     fn output_progress_ui(&mut self, ui: &mut Ui) {
         let Ok(output_progress) = self.output_progress.lock() else {
             return;
@@ -385,70 +729,229 @@ impl IblSamplerApp {
         match *output_progress {
             OutputProgress::NotStartedYet => {}
 
-            OutputProgress::Succeeded { output_count } => {
+            OutputProgress::Succeeded {
+                output_count,
+                disabled_count,
+                unchanged_count,
+                ..
+            } => {
                 if output_count == 1 {
                     ui.label(&t!("output.progress.success.single"));
                 } else {
                     ui.label(&t!("output.progress.success.multi", count = output_count));
                 }
+                if disabled_count > 0 {
+                    ui.label(&t!("output.progress.skipped", count = disabled_count));
+                }
+                if unchanged_count > 0 {
+                    ui.label(t!("output.progress.unchanged", count = unchanged_count));
+                }
             }
 
-            OutputProgress::InProgress {
-                input_reencoding_status,
-                outputs_finished,
-                output_count,
-            } => {
-                let mut progress = match input_reencoding_status {
-                    InputReencodingStatus::Loading => 0.0 / 3.0,
-                    InputReencodingStatus::Resizing => 1.0 / 3.0,
-                    InputReencodingStatus::Writing => 2.0 / 3.0,
-                    InputReencodingStatus::Reencoded => 3.0 / 3.0,
-                };
-                progress += outputs_finished as f32;
+            OutputProgress::InProgress { ref outputs, .. } => {
                 ui.add(
-                    ProgressBar::new(progress / (output_count + 1) as f32)
+                    ProgressBar::new(progress_fraction(&output_progress))
                         .show_percentage()
                         .animate(true),
                 );
+
+                if let Some((output_index, elapsed, sampler_progress)) = running_output(outputs) {
+                    let label = match sampler_progress {
+                        None => t!(
+                            "output.progress.running",
+                            index = output_index + 1,
+                            elapsed = format_duration(elapsed)
+                        ),
+                        Some(sampler_progress) => {
+                            // Assume that the rest of the output goes as fast as what's done.
+                            let fraction = sampler_progress.fraction();
+                            let remaining = elapsed.mul_f32((1.0 - fraction) / fraction);
+                            t!(
+                                "output.progress.sampling",
+                                index = output_index + 1,
+                                level = sampler_progress.mip_level + 1,
+                                level_count = sampler_progress.mip_level_count,
+                                face = sampler_progress.face + 1,
+                                elapsed = format_duration(elapsed),
+                                remaining = format_duration(remaining)
+                            )
+                        }
+                    };
+                    ui.label(label);
+                    // Keep the elapsed time ticking.
+                    ui.ctx().request_repaint_after(Duration::from_millis(250));
+                }
             }
 
-            OutputProgress::Failed {
-                which_failed,
-                ref error,
-            } => {
-                ui.colored_label(
-                    Color32::RED,
-                    &t!(
+            OutputProgress::Failed { ref error, .. } => {
+                let message = error.kind.to_localized_string();
+                let message = match error.output_index {
+                    Some(output_index) => t!(
                         "output.progress.failure",
-                        index = (which_failed + 1),
-                        error = error
+                        index = (output_index + 1),
+                        error = message
                     ),
-                );
+                    None => t!("output.progress.export.failure", error = message),
+                };
+                ui.colored_label(Color32::RED, message);
+
+                if ui
+                    .small_button(&t!("button.copy.error.details"))
+                    .on_hover_text(t!("help.button.copy.error.details"))
+                    .clicked()
+                {
+                    ui.ctx().copy_text(error.details());
+                }
             }
         }
     }
 
+    fn output_results_ui(&mut self, ui: &mut Ui) {
+        // Copy the statuses, so that the generator isn't blocked while an error is shown.
+        let outputs: Vec<OutputStatus> = self.output_progress.lock().unwrap().outputs().to_vec();
+        if outputs.is_empty() {
+            return;
+        }
+
+        CollapsingHeader::new(t!("results.header"))
+            .id_source("IblResults")
+            .default_open(false)
+            .show(ui, |ui| {
+                Grid::new("IblResultsGrid")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (output_index, output) in outputs.iter().enumerate() {
+                            output_result_ui(ui, output_index, output);
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
     /// Returns true if the files changed and false otherwise.
     fn output_ui(&mut self, ui: &mut Ui, output_index: usize) -> bool {
         let output = &mut self.job.outputs[output_index];
+        // Automatic file names can depend on any of the settings.
+        let old_output = output.clone();
+        let issues = &self.validation_issues;
+        let field = |output_field| JobField::Output(output_index, output_field);
 
         let mut files_changed = false;
 
         let grid_id = format!("IblOutput{}", output_index);
         Grid::new(grid_id).num_columns(2).show(ui, |ui| {
+            // Offer the file type of the current container first.
+            let mut cubemap_filters = [
+                (&*t!("output.file.ktx2"), "ktx2"),
+                (&*t!("output.file.ktx1"), "ktx1"),
+                (&*t!("output.file.dds"), "dds"),
+                (&*t!("output.file.exr"), "exr"),
+                (&*t!("output.file.hdr"), "hdr"),
+            ];
+            let current_filter = cubemap_filters
+                .iter()
+                .position(|&(_, extension)| extension == output.container_format.extension())
+                .unwrap_or(0);
+            cubemap_filters[..=current_filter].rotate_right(1);
+
             if output_file_picker(
                 ui,
                 &mut output.out_cubemap.path,
                 &t!("output.cubemap"),
                 Some(&t!("help.output.cubemap")),
-                &[
-                    (&*t!("output.file.ktx2"), "ktx2"),
-                    (&*t!("output.file.ktx1"), "ktx1"),
-                ],
+                &cubemap_filters,
             ) {
                 output.out_cubemap.automatic_filename = false;
+                // Don't switch between the EXR options just because an `.exr` file was chosen.
+                if let Some(container_format) = ContainerFormat::from_path(&output.out_cubemap.path)
+                {
+                    if container_format.extension() != output.container_format.extension() {
+                        output.container_format = container_format;
+                    }
+                }
                 files_changed = true;
             }
+            field_issues_ui(ui, issues, field(OutputField::CubemapPath));
+
+            // File name template
+            ui.label(&t!("output.filename.template"));
+            if ui
+                .add_sized(
+                    [ui.available_width(), 0.0],
+                    TextEdit::singleline(&mut output.filename_template)
+                        .hint_text(filename::DEFAULT_TEMPLATE),
+                )
+                .on_hover_text(layout_text_with_code(&t!("help.output.filename.template")))
+                .changed()
+            {
+                output.out_cubemap.automatic_filename = true;
+                if let Some(ref mut filter_settings) = output.filter_settings {
+                    filter_settings.out_lut.automatic_filename = true;
+                }
+                files_changed = true;
+            }
+            ui.end_row();
+
+            // Container format
+            let old_container_format = output.container_format;
+            output_enum(
+                ui,
+                &mut output.container_format,
+                &t!("output.container.format"),
+                output_index,
+                &[
+                    ContainerFormat::Ktx2,
+                    ContainerFormat::Dds,
+                    ContainerFormat::ExrMultiPart,
+                    ContainerFormat::ExrPerLevel,
+                    ContainerFormat::HdrPerLevel,
+                ],
+                Some(&t!("help.output.container.format")),
+            );
+            if output.container_format != old_container_format {
+                output
+                    .out_cubemap
+                    .path
+                    .set_extension(output.container_format.extension());
+                files_changed = true;
+            }
+
+            // Projection
+            output_enum(
+                ui,
+                &mut output.projection,
+                &t!("output.projection"),
+                output_index,
+                &[
+                    Projection::Cubemap,
+                    Projection::Equirectangular,
+                    Projection::Octahedral,
+                ],
+                Some(&t!("help.output.projection")),
+            );
+
+            if output.projection == Projection::Octahedral {
+                // Octahedral resolution
+                output_optional_numeric_value_ui(
+                    ui,
+                    output_index,
+                    &t!("output.octahedral.resolution"),
+                    "IblOctahedralResolution",
+                    &mut output.octahedral_settings.resolution,
+                    2048,
+                    Some(&t!("help.output.octahedral.resolution")),
+                );
+                field_issues_ui(ui, issues, field(OutputField::OctahedralResolution));
+
+                // Octahedral border
+                output_numeric_value_ui(
+                    ui,
+                    &mut output.octahedral_settings.border,
+                    &t!("output.octahedral.border"),
+                    Some(&t!("help.output.octahedral.border")),
+                );
+            }
 
             // Mipmap levels
             output_optional_numeric_value_ui(
@@ -460,6 +963,7 @@ impl IblSamplerApp {
                 3,
                 Some(&t!("help.output.mipmap.levels")),
             );
+            field_issues_ui(ui, issues, field(OutputField::MipLevelCount));
 
             // Cubemap resolution
             output_optional_numeric_value_ui(
@@ -471,21 +975,27 @@ impl IblSamplerApp {
                 1024,
                 Some(&t!("help.output.cubemap.resolution")),
             );
+            field_issues_ui(ui, issues, field(OutputField::CubemapResolution));
 
             // Target format
-            output_enum(
-                ui,
-                &mut output.target_format,
-                &t!("output.target.format"),
-                output_index,
-                &[
-                    TargetFormat::R8G8B8A8Unorm,
-                    TargetFormat::R9G9B9E5Ufloat,
-                    TargetFormat::R16G16B16A16Sfloat,
-                    TargetFormat::R32G32B32A32Sfloat,
-                ],
-                Some(&t!("help.output.target.format")),
-            );
+            if output.container_format.uses_target_format() {
+                output_enum(
+                    ui,
+                    &mut output.target_format,
+                    &t!("output.target.format"),
+                    output_index,
+                    &[
+                        TargetFormat::R8G8B8A8Unorm,
+                        TargetFormat::R9G9B9E5Ufloat,
+                        TargetFormat::R16G16B16A16Sfloat,
+                        TargetFormat::R32G32B32A32Sfloat,
+                        TargetFormat::Astc4x4Sfloat,
+                        TargetFormat::Astc6x6Sfloat,
+                    ],
+                    Some(&t!("help.output.target.format")),
+                );
+                field_issues_ui(ui, issues, field(OutputField::TargetFormat));
+            }
 
             // LOD bias
             output_numeric_value_ui(
@@ -509,6 +1019,7 @@ impl IblSamplerApp {
                     filter_settings.out_lut.automatic_filename = false;
                     files_changed = true;
                 }
+                field_issues_ui(ui, issues, field(OutputField::LutPath));
 
                 // Sample count
                 output_numeric_value_ui(
@@ -517,13 +1028,606 @@ impl IblSamplerApp {
                     &t!("output.sample.count"),
                     Some(&t!("help.output.sample.count")),
                 );
+                field_issues_ui(ui, issues, field(OutputField::SampleCount));
+            }
+        });
+
+        // Changing the distribution resets the filter settings, so make sure that it can be
+        // undone on its own.
+        let distribution = |output: &Output| {
+            output
+                .filter_settings
+                .as_ref()
+                .map(|filter_settings| filter_settings.distribution)
+        };
+        if distribution(output) != distribution(&old_output) {
+            let mut old_job = self.job.clone();
+            old_job.outputs[output_index] = old_output.clone();
+            self.history.add_undo(&old_job);
+        }
+
+        let output = &self.job.outputs[output_index];
+        files_changed || *output != old_output
+    }
+
+    /// Returns true if the files changed and false otherwise.
+    fn gltf_export_ui(&mut self, ui: &mut Ui) -> bool {
+        let gltf_export = &mut self.job.gltf_export;
+        let issues = &self.validation_issues;
+
+        let mut files_changed = false;
+
+        Grid::new("IblGltfExport").num_columns(2).show(ui, |ui| {
+            output_enum(
+                ui,
+                &mut gltf_export.mode,
+                &t!("gltf.export.mode"),
+                0,
+                &[
+                    GltfExportMode::Disabled,
+                    GltfExportMode::Snippet,
+                    GltfExportMode::Inject,
+                ],
+                Some(&t!("help.gltf.export.mode")),
+            );
+            field_issues_ui(ui, issues, JobField::GltfExport);
+
+            match gltf_export.mode {
+                GltfExportMode::Disabled => return,
+                GltfExportMode::Snippet => {
+                    if output_file_picker(
+                        ui,
+                        &mut gltf_export.snippet_path.path,
+                        &t!("gltf.export.snippet"),
+                        Some(&t!("help.gltf.export.snippet")),
+                        &[(&*t!("gltf.file.json"), "json")],
+                    ) {
+                        gltf_export.snippet_path.automatic_filename = false;
+                        files_changed = true;
+                    }
+                    field_issues_ui(ui, issues, JobField::GltfSnippetPath);
+                }
+                GltfExportMode::Inject => {
+                    output_file_picker(
+                        ui,
+                        &mut gltf_export.gltf_path,
+                        &t!("gltf.export.gltf"),
+                        Some(&t!("help.gltf.export.gltf")),
+                        &[(&*t!("gltf.file.gltf"), "gltf")],
+                    );
+                    field_issues_ui(ui, issues, JobField::GltfPath);
+                }
+            }
+
+            // Rotation
+            output_numeric_value_ui(
+                ui,
+                &mut gltf_export.rotation,
+                &t!("gltf.export.rotation"),
+                Some(&t!("help.gltf.export.rotation")),
+            );
+
+            // Intensity
+            output_numeric_value_ui(
+                ui,
+                &mut gltf_export.intensity,
+                &t!("gltf.export.intensity"),
+                Some(&t!("help.gltf.export.intensity")),
+            );
+        });
+
+        files_changed
+    }
+
+    /// Returns true if the files changed and false otherwise.
+    fn bevy_bundle_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut files_changed = false;
+
+        Grid::new("IblBevyBundle").num_columns(2).show(ui, |ui| {
+            let bevy_bundle = &mut self.job.bevy_bundle;
+            let issues = &self.validation_issues;
+
+            output_enum(
+                ui,
+                &mut bevy_bundle.version,
+                &t!("bevy.version"),
+                0,
+                &[
+                    None,
+                    Some(BevyVersion::V0_12),
+                    Some(BevyVersion::V0_13),
+                    Some(BevyVersion::V0_14),
+                ],
+                Some(&t!("help.bevy.version")),
+            );
+            field_issues_ui(ui, issues, JobField::BevyBundle);
+
+            if bevy_bundle.version.is_none() {
+                return;
+            }
+
+            if output_file_picker(
+                ui,
+                &mut bevy_bundle.snippet_path.path,
+                &t!("bevy.snippet"),
+                Some(&t!("help.bevy.snippet")),
+                &[(&*t!("bevy.file.rs"), "rs")],
+            ) {
+                bevy_bundle.snippet_path.automatic_filename = false;
+                files_changed = true;
+            }
+            field_issues_ui(ui, issues, JobField::BevySnippetPath);
+
+            // Intensity
+            output_numeric_value_ui(
+                ui,
+                &mut bevy_bundle.intensity,
+                &t!("bevy.intensity"),
+                Some(&t!("help.bevy.intensity")),
+            );
+
+            ui.label("");
+            if ui
+                .button(&t!("bevy.apply.preset"))
+                .on_hover_text(layout_text_with_code(&t!("help.bevy.apply.preset")))
+                .clicked()
+            {
+                self.checkpoint();
+                self.job.outputs = bevy::preset_outputs();
+                files_changed = true;
+            }
+            ui.end_row();
+        });
+
+        files_changed
+    }
+
+    /// Returns true if the files changed and false otherwise.
+    fn presets_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut files_changed = false;
+        let mut preset_to_delete = None;
+
+        Grid::new("IblPresets").num_columns(2).show(ui, |ui| {
+            ui.label(&t!("preset.apply"));
+            let mut preset_to_apply = None;
+            ComboBox::from_id_source("IblPresetApply")
+                .selected_text(t!("preset.choose"))
+                .show_ui(ui, |ui| {
+                    for preset in presets::built_in_presets() {
+                        if ui.selectable_label(false, &preset.name).clicked() {
+                            preset_to_apply = Some(preset.outputs);
+                        }
+                    }
+
+                    if !self.user_presets.is_empty() {
+                        ui.separator();
+                    }
+                    for (preset_index, preset) in self.user_presets.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .small_button("🗑")
+                                .on_hover_text(t!("help.preset.delete"))
+                                .clicked()
+                            {
+                                preset_to_delete = Some(preset_index);
+                            }
+                            if ui.selectable_label(false, &preset.name).clicked() {
+                                preset_to_apply = Some(preset.outputs.clone());
+                            }
+                        });
+                    }
+                })
+                .response
+                .on_hover_text(layout_text_with_code(&t!("help.preset.apply")));
+            ui.end_row();
+
+            if let Some(outputs) = preset_to_apply {
+                self.checkpoint();
+                self.job.outputs = outputs;
+                files_changed = true;
             }
+
+            ui.label(&t!("preset.save"));
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                let name = self.new_preset_name.trim();
+                if ui
+                    .add_enabled(!name.is_empty(), Button::new(t!("preset.save.button")))
+                    .on_hover_text(layout_text_with_code(&t!("help.preset.save")))
+                    .clicked()
+                {
+                    let preset = Preset::new(name.to_owned(), &self.job.outputs);
+                    match self
+                        .user_presets
+                        .iter_mut()
+                        .find(|existing| existing.name == preset.name)
+                    {
+                        Some(existing) => *existing = preset,
+                        None => self.user_presets.push(preset),
+                    }
+                    self.new_preset_name.clear();
+                }
+
+                ui.add_sized(
+                    ui.available_size(),
+                    TextEdit::singleline(&mut self.new_preset_name)
+                        .hint_text(t!("preset.save.name")),
+                )
+                .on_hover_text(layout_text_with_code(&t!("help.preset.save")));
+            });
+            ui.end_row();
         });
 
+        if let Some(preset_index) = preset_to_delete {
+            self.user_presets.remove(preset_index);
+        }
+
         files_changed
     }
 
     /// NB: When you call this, make sure to set `files_changed` to true.
+    fn file_menu_ui(&mut self, ui: &mut Ui) {
+        if ui.button(&t!("menu.file.new")).clicked() {
+            ui.close_menu();
+            self.new_job(ui.ctx());
+        }
+
+        if ui
+            .add(
+                Button::new(t!("menu.file.open"))
+                    .shortcut_text(ui.ctx().format_shortcut(&OPEN_SHORTCUT)),
+            )
+            .clicked()
+        {
+            ui.close_menu();
+            self.open_job_dialog(ui.ctx());
+        }
+
+        ui.add_enabled_ui(!self.recent_jobs.is_empty(), |ui| {
+            ui.menu_button(&t!("menu.file.open.recent"), |ui| {
+                let mut path_to_open = None;
+                for path in self.recent_jobs.paths() {
+                    if ui
+                        .button(path.display().to_string())
+                        .on_hover_text(path.display().to_string())
+                        .clicked()
+                    {
+                        path_to_open = Some(path.to_owned());
+                    }
+                }
+                if let Some(path) = path_to_open {
+                    ui.close_menu();
+                    self.open_job(ui.ctx(), path);
+                }
+
+                ui.separator();
+                if ui.button(&t!("menu.file.open.recent.clear")).clicked() {
+                    ui.close_menu();
+                    self.recent_jobs.clear();
+                }
+            });
+        });
+
+        ui.separator();
+
+        if ui
+            .add(
+                Button::new(t!("menu.file.save"))
+                    .shortcut_text(ui.ctx().format_shortcut(&SAVE_SHORTCUT)),
+            )
+            .clicked()
+        {
+            ui.close_menu();
+            self.save_job();
+        }
+
+        if ui
+            .add(
+                Button::new(t!("menu.file.save.as"))
+                    .shortcut_text(ui.ctx().format_shortcut(&SAVE_AS_SHORTCUT)),
+            )
+            .clicked()
+        {
+            ui.close_menu();
+            self.save_job_as();
+        }
+
+        ui.separator();
+
+        if ui
+            .button(&t!("menu.file.copy.job"))
+            .on_hover_text(layout_text_with_code(&t!("help.menu.file.copy.job")))
+            .clicked()
+        {
+            ui.close_menu();
+            match job_file::encode(&self.job) {
+                Ok(encoded_job) => ui.ctx().copy_text(encoded_job),
+                Err(error) => warn!("Failed to copy the job: {}", error),
+            }
+        }
+
+        if ui
+            .button(&t!("menu.file.paste.job"))
+            .on_hover_text(layout_text_with_code(&t!("help.menu.file.paste.job")))
+            .clicked()
+        {
+            ui.close_menu();
+            self.paste_job(ui.ctx());
+        }
+    }
+
+    fn edit_menu_ui(&mut self, ui: &mut Ui) {
+        if ui
+            .add_enabled(
+                self.history.has_undo(&self.job),
+                Button::new(t!("menu.edit.undo"))
+                    .shortcut_text(ui.ctx().format_shortcut(&UNDO_SHORTCUT)),
+            )
+            .clicked()
+        {
+            ui.close_menu();
+            self.undo(ui.ctx());
+        }
+
+        if ui
+            .add_enabled(
+                self.history.has_redo(&self.job),
+                Button::new(t!("menu.edit.redo"))
+                    .shortcut_text(ui.ctx().format_shortcut(&REDO_SHORTCUT)),
+            )
+            .clicked()
+        {
+            ui.close_menu();
+            self.redo(ui.ctx());
+        }
+    }
+
+    fn shortcuts(&mut self, ctx: &Context) {
+        // These are consumed before any widgets are shown, so they undo edits to the job even
+        // while a text field has focus, rather than only the text field's own edits.
+        if ctx.input_mut(|input| input.consume_shortcut(&REDO_SHORTCUT)) {
+            self.redo(ctx);
+        }
+        if ctx.input_mut(|input| input.consume_shortcut(&UNDO_SHORTCUT)) {
+            self.undo(ctx);
+        }
+
+        if ctx.input_mut(|input| input.consume_shortcut(&OPEN_SHORTCUT)) {
+            self.open_job_dialog(ctx);
+        }
+        // Check for the longer shortcut first, since it contains the shorter one.
+        if ctx.input_mut(|input| input.consume_shortcut(&SAVE_AS_SHORTCUT)) {
+            self.save_job_as();
+        }
+        if ctx.input_mut(|input| input.consume_shortcut(&SAVE_SHORTCUT)) {
+            self.save_job();
+        }
+    }
+
+    // Checks the job for problems if it's changed since it was last checked. Files may have been
+    // created or deleted in the meantime by other apps, so it's also checked again whenever the
+    // window regains focus.
+    fn validate_job(&mut self, ctx: &Context) {
+        let window_focused = ctx.input(|input| input.viewport().focused.unwrap_or(true));
+        if window_focused && !self.window_focused {
+            self.validated_job = None;
+        }
+        self.window_focused = window_focused;
+
+        if self.validated_job.as_ref() != Some(&self.job) {
+            self.validation_issues = self.job.validate();
+            self.validated_job = Some(self.job.clone());
+        }
+    }
+
+    fn update_title(&mut self, ctx: &Context) {
+        let name = match self.job_path {
+            Some(ref job_path) => job_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            None => t!("job.untitled"),
+        };
+        let title = t!(
+            "app.title.job",
+            name = name,
+            modified = (if self.job_has_unsaved_changes() {
+                "*"
+            } else {
+                ""
+            }),
+            app = t!("app.title")
+        );
+
+        if title != self.title {
+            ctx.send_viewport_cmd(ViewportCommand::Title(title.clone()));
+            self.title = title;
+        }
+    }
+
+    // Adds an undo point for the job as it is, so that the change that's about to be made can be
+    // undone separately from earlier edits.
+    fn checkpoint(&mut self) {
+        self.history.add_undo(&self.job);
+    }
+
+    fn undo(&mut self, ctx: &Context) {
+        if let Some(job) = self.history.undo(&self.job) {
+            let job = job.clone();
+            self.restore_job(ctx, job);
+        }
+    }
+
+    fn redo(&mut self, ctx: &Context) {
+        if let Some(job) = self.history.redo(&self.job) {
+            let job = job.clone();
+            self.restore_job(ctx, job);
+        }
+    }
+
+    fn restore_job(&mut self, ctx: &Context, job: Job) {
+        let input_changed = job.input_path != self.job.input_path;
+        self.job = job;
+        if input_changed {
+            self.load_input_preview(ctx);
+        }
+    }
+
+    fn job_has_unsaved_changes(&self) -> bool {
+        self.job != self.saved_job
+    }
+
+    fn new_job(&mut self, ctx: &Context) {
+        if self.confirm_discard_changes() {
+            self.replace_job(ctx, Job::default(), None);
+        }
+    }
+
+    fn open_job_dialog(&mut self, ctx: &Context) {
+        if let Some(path) = FileDialog::new()
+            .add_filter(
+                t!("job.file.type"),
+                &[job_file::RON_EXTENSION, job_file::JSON_EXTENSION],
+            )
+            .add_filter(t!("job.file.type.manifest"), &[job_file::JSON_EXTENSION])
+            .pick_file()
+        {
+            self.open_job(ctx, path);
+        }
+    }
+
+    fn open_job(&mut self, ctx: &Context, path: PathBuf) {
+        if !self.confirm_discard_changes() {
+            return;
+        }
+
+        // Jobs opened from an output's manifest are new jobs, so that saving them doesn't
+        // overwrite the manifest.
+        if manifest::is_manifest(&path) {
+            match manifest::load_job(&path) {
+                Ok(job) => {
+                    self.replace_job(ctx, job, None);
+                    self.saved_job = Job::default();
+                }
+                Err(error) => show_error(&t!(
+                    "job.error.open.manifest",
+                    path = (path.display()),
+                    error = error
+                )),
+            }
+            return;
+        }
+
+        match job_file::load(&path) {
+            Ok(job) => {
+                self.recent_jobs.add(&path);
+                self.replace_job(ctx, job, Some(path));
+            }
+            Err(error) => {
+                if !path.exists() {
+                    self.recent_jobs.remove(&path);
+                }
+                show_error(&t!(
+                    "job.error.open",
+                    path = (path.display()),
+                    error = error
+                ));
+            }
+        }
+    }
+
+    /// Returns true if the job was saved.
+    fn save_job(&mut self) -> bool {
+        match self.job_path.clone() {
+            Some(job_path) => self.save_job_to(job_path),
+            None => self.save_job_as(),
+        }
+    }
+
+    /// Returns true if the job was saved.
+    fn save_job_as(&mut self) -> bool {
+        let mut file_name = self
+            .job
+            .input_path
+            .file_stem()
+            .unwrap_or(OsStr::new("job"))
+            .to_owned();
+        file_name.push(".");
+        file_name.push(job_file::RON_EXTENSION);
+
+        match FileDialog::new()
+            .add_filter(t!("job.file.type.ron"), &[job_file::RON_EXTENSION])
+            .add_filter(t!("job.file.type.json"), &[job_file::JSON_EXTENSION])
+            .set_file_name(file_name.to_string_lossy())
+            .save_file()
+        {
+            Some(path) => self.save_job_to(path),
+            None => false,
+        }
+    }
+
+    fn save_job_to(&mut self, path: PathBuf) -> bool {
+        if let Err(error) = job_file::save(&self.job, &path) {
+            show_error(&t!(
+                "job.error.save",
+                path = (path.display()),
+                error = error
+            ));
+            return false;
+        }
+
+        self.saved_job = self.job.clone();
+        self.recent_jobs.add(&path);
+        self.job_path = Some(path);
+        true
+    }
+
+    fn paste_job(&mut self, ctx: &Context) {
+        let job = arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .map_err(Error::from)
+            .and_then(|text| job_file::decode(&text));
+        match job {
+            Ok(job) => {
+                if self.confirm_discard_changes() {
+                    // A pasted job hasn't been saved anywhere yet.
+                    self.replace_job(ctx, job, None);
+                    self.saved_job = Job::default();
+                }
+            }
+            Err(error) => show_error(&t!("job.error.paste", error = error)),
+        }
+    }
+
+    fn replace_job(&mut self, ctx: &Context, job: Job, job_path: Option<PathBuf>) {
+        self.saved_job = job.clone();
+        self.job = job;
+        self.job_path = job_path;
+        self.history = Undoer::default();
+        *self.output_progress.lock().unwrap() = OutputProgress::NotStartedYet;
+        self.load_input_preview(ctx);
+    }
+
+    /// Asks the user whether to save the job if it has unsaved changes. Returns false if the user
+    /// cancelled.
+    fn confirm_discard_changes(&mut self) -> bool {
+        if !self.job_has_unsaved_changes() {
+            return true;
+        }
+
+        match MessageDialog::new()
+            .set_title(t!("app.title"))
+            .set_level(MessageLevel::Warning)
+            .set_buttons(MessageButtons::YesNoCancel)
+            .set_description(t!("job.unsaved.changes"))
+            .show()
+        {
+            MessageDialogResult::Yes => self.save_job(),
+            MessageDialogResult::No => true,
+            _ => false,
+        }
+    }
+
     fn set_input_path(&mut self, ctx: &Context, input_path: PathBuf) {
         self.job.input_path = input_path;
 
@@ -531,13 +1635,14 @@ impl IblSamplerApp {
     }
 
     fn load_input_preview(&mut self, ctx: &Context) {
+        let texture_slot = self.input_preview.clone();
+
         // Early out if this can't possibly succeed.
         if &*self.job.input_path == Path::new("") {
+            texture_slot.lock().unwrap().payload = InputPreviewPayload::NoneSelected;
             return;
         }
 
-        let texture_slot = self.input_preview.clone();
-
         let epoch;
         {
             let mut texture_slot_inner = texture_slot.lock().unwrap();
@@ -609,51 +1714,222 @@ impl IblSamplerApp {
         else {
             return;
         };
-        let file_stem = input_path.file_stem().unwrap_or(OsStr::new(""));
+        let file_stem = input_path.file_stem().unwrap_or_default();
+        let template_context = TemplateContext::new(file_stem);
 
         // Determine other filenames.
         let mut used = HashSet::new();
         for output in &mut self.job.outputs {
-            let suffix = match output
-                .filter_settings
-                .as_ref()
-                .map(|filter_settings| filter_settings.distribution)
-            {
-                None => "cubemap",
-                Some(Distribution::Lambertian) => "diffuse",
-                Some(Distribution::Ggx) => "specular",
-                Some(Distribution::Charlie) => "charlie",
-            };
+            let kind = filename::kind(output).to_owned();
 
             if output.out_cubemap.automatic_filename {
-                if let Some(cubemap_path) = create_output_path(
-                    &output_dir,
-                    file_stem,
-                    suffix,
-                    &mut used,
-                    /*is_lut=*/ false,
-                ) {
+                let file_name = filename::expand_template(
+                    output,
+                    &template_context,
+                    &kind,
+                    output.container_format.extension(),
+                );
+                if let Some(cubemap_path) = create_output_path(&output_dir, &file_name, &mut used) {
                     output.out_cubemap.path = cubemap_path;
                 }
             }
 
+            let lut_file_name = filename::expand_template(
+                output,
+                &template_context,
+                &format!("{}_lut", kind),
+                "png",
+            );
             if let Some(ref mut filter_settings) = output.filter_settings {
                 if filter_settings.out_lut.automatic_filename {
-                    if let Some(lut_path) = create_output_path(
-                        &output_dir,
-                        file_stem,
-                        &format!("{}_lut", suffix),
-                        &mut used,
-                        /*is_lut=*/ true,
-                    ) {
+                    if let Some(lut_path) =
+                        create_output_path(&output_dir, &lut_file_name, &mut used)
+                    {
                         filter_settings.out_lut.path = lut_path;
                     }
                 }
             }
         }
+
+        let snippet_path = &mut self.job.gltf_export.snippet_path;
+        if snippet_path.automatic_filename {
+            let mut file_name = file_stem.to_owned();
+            file_name.push("_light.json");
+            if let Some(path) = create_output_path(&output_dir, &file_name, &mut used) {
+                snippet_path.path = path;
+            }
+        }
+
+        let snippet_path = &mut self.job.bevy_bundle.snippet_path;
+        if snippet_path.automatic_filename {
+            let mut file_name = file_stem.to_owned();
+            file_name.push("_bevy.rs");
+            if let Some(path) = create_output_path(&output_dir, &file_name, &mut used) {
+                snippet_path.path = path;
+            }
+        }
+    }
+
+    // Returns true if the job being edited or a job from the queue is being generated. Only one
+    // job runs at a time, since the sampler's output is captured for the whole process.
+    fn job_in_progress(&self) -> bool {
+        [&self.output_progress, &self.queue_progress]
+            .iter()
+            .any(|progress| matches!(*progress.lock().unwrap(), OutputProgress::InProgress { .. }))
+    }
+
+    // Records how the running job from the queue ended, and starts the next one if nothing else
+    // is running.
+    fn run_queue(&mut self, ctx: &Context) {
+        self.queue.finish(&self.queue_progress.lock().unwrap());
+        if self.job_in_progress() {
+            return;
+        }
+
+        if let Some(job) = self.queue.start_next() {
+            self.queue_worker = generator::generate(ctx, job.clone(), self.queue_progress.clone());
+        }
+    }
+
+    fn queue_window_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let pause_text = if self.queue.paused {
+                t!("button.queue.resume")
+            } else {
+                t!("button.queue.pause")
+            };
+            if ui
+                .button(pause_text)
+                .on_hover_text(t!("help.button.queue.pause"))
+                .clicked()
+            {
+                self.queue.paused = !self.queue.paused;
+            }
+
+            if self.queue_worker.is_running()
+                && ui
+                    .button(t!("button.stop"))
+                    .on_hover_text(t!("help.button.stop"))
+                    .clicked()
+            {
+                self.queue_worker.stop();
+            }
+
+            if ui
+                .button(t!("button.queue.clear.done"))
+                .on_hover_text(t!("help.button.queue.clear.done"))
+                .clicked()
+            {
+                self.queue.clear_done();
+            }
+        });
+        ui.separator();
+
+        if self.queue.jobs.is_empty() {
+            ui.label(t!("queue.empty"));
+            return;
+        }
+
+        let job_count = self.queue.jobs.len();
+        let mut action = None;
+        Grid::new("IblQueueGrid")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for (index, queued_job) in self.queue.jobs.iter().enumerate() {
+                    let name = queued_job
+                        .job
+                        .input_path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    ui.label(format!("{}. {}", index + 1, name))
+                        .on_hover_text(queued_job.job.input_path.display().to_string());
+
+                    let running = matches!(queued_job.state, QueuedJobState::Running);
+                    match queued_job.state {
+                        QueuedJobState::Waiting => {
+                            ui.label(t!("queue.state.waiting"));
+                        }
+                        QueuedJobState::Running => {
+                            let progress = progress_fraction(&self.queue_progress.lock().unwrap());
+                            ui.add(ProgressBar::new(progress).show_percentage().animate(true));
+                        }
+                        QueuedJobState::Done => {
+                            ui.label(t!("queue.state.done"));
+                        }
+                        QueuedJobState::Failed(ref error) => {
+                            ui.colored_label(Color32::RED, t!("queue.state.failed"))
+                                .on_hover_text(error.kind.to_localized_string());
+                        }
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(index > 0, Button::new("⏶"))
+                            .on_hover_text(t!("help.queue.move.up"))
+                            .clicked()
+                        {
+                            action = Some(QueueAction::Move(index, index - 1));
+                        }
+                        if ui
+                            .add_enabled(index + 1 < job_count, Button::new("⏷"))
+                            .on_hover_text(t!("help.queue.move.down"))
+                            .clicked()
+                        {
+                            action = Some(QueueAction::Move(index, index + 1));
+                        }
+                        if matches!(queued_job.state, QueuedJobState::Failed(_))
+                            && ui
+                                .button("↻")
+                                .on_hover_text(t!("help.queue.retry"))
+                                .clicked()
+                        {
+                            action = Some(QueueAction::Retry(index));
+                        }
+                        if ui
+                            .button("✏")
+                            .on_hover_text(t!("help.queue.edit"))
+                            .clicked()
+                        {
+                            action = Some(QueueAction::Edit(index));
+                        }
+                        if ui
+                            .add_enabled(!running, Button::new("🗑"))
+                            .on_hover_text(t!("help.queue.remove"))
+                            .clicked()
+                        {
+                            action = Some(QueueAction::Remove(index));
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+
+        match action {
+            Some(QueueAction::Move(from, to)) => self.queue.move_job(from, to),
+            Some(QueueAction::Retry(index)) => self.queue.retry(index),
+            Some(QueueAction::Edit(index)) if self.confirm_discard_changes() => {
+                let job = self.queue.jobs[index].job.clone();
+                // The copy in the editor hasn't been saved anywhere yet.
+                self.replace_job(ui.ctx(), job, None);
+                self.saved_job = Job::default();
+            }
+            Some(QueueAction::Remove(index)) => {
+                self.queue.jobs.remove(index);
+            }
+            _ => {}
+        }
     }
 
     fn log_window_ui(&mut self, ui: &mut Ui) {
+        ui.checkbox(
+            &mut self.job.keep_temporary_files,
+            t!("log.keep.temporary.files"),
+        )
+        .on_hover_text(layout_text_with_code(&t!("help.log.keep.temporary.files")));
+        ui.separator();
+
         // Clone the messages so we don't deadlock if egui logs internally.
         let messages = match LOG_BUFFER.0.lock() {
             Err(_) => return,
@@ -668,11 +1944,35 @@ impl IblSamplerApp {
     /// Shows a confirmation dialog box if any overwriting is going to occur. Returns true if the
     /// user authorized the change.
     fn check_for_overwrite_and_prompt_user(&mut self) -> bool {
+        // Outputs that are up to date are skipped, so their files won't be touched. Don't bother
+        // hashing the input if nothing can be skipped.
+        let input_hash = if self.job.force_rebuild {
+            None
+        } else {
+            manifest::hash_file(&self.job.input_path).ok()
+        };
+
         let mut paths_to_overwrite = vec![];
-        for output in &self.job.outputs {
+        for (output_index, output) in self.job.outputs.iter().enumerate() {
+            if !output.enabled
+                || self
+                    .job
+                    .unchanged_output(output_index, input_hash.as_deref())
+                    .is_some()
+            {
+                continue;
+            }
+
             if output.out_cubemap.path.exists() {
                 paths_to_overwrite.push(output.out_cubemap.path.clone());
             }
+            if output.container_format.has_file_per_level() {
+                paths_to_overwrite.extend(
+                    (1..)
+                        .map(|level| texture::mip_level_path(&output.out_cubemap.path, level))
+                        .take_while(|path| path.exists()),
+                );
+            }
             if let Some(ref filter_settings) = output.filter_settings {
                 if filter_settings.out_lut.path.exists() {
                     paths_to_overwrite.push(filter_settings.out_lut.path.clone());
@@ -680,6 +1980,24 @@ impl IblSamplerApp {
             }
         }
 
+        let gltf_export = &self.job.gltf_export;
+        let gltf_path = match gltf_export.mode {
+            GltfExportMode::Disabled => None,
+            GltfExportMode::Snippet => Some(&gltf_export.snippet_path.path),
+            GltfExportMode::Inject => Some(&gltf_export.gltf_path),
+        };
+        if let Some(gltf_path) = gltf_path {
+            if gltf_path.exists() {
+                paths_to_overwrite.push(gltf_path.clone());
+            }
+            paths_to_overwrite.extend(gltf::existing_image_paths(gltf_path));
+        }
+
+        let bevy_bundle = &self.job.bevy_bundle;
+        if bevy_bundle.version.is_some() && bevy_bundle.snippet_path.path.exists() {
+            paths_to_overwrite.push(bevy_bundle.snippet_path.path.clone());
+        }
+
         if paths_to_overwrite.is_empty() {
             return true;
         }
@@ -710,27 +2028,28 @@ impl IblSamplerApp {
     }
 }
 
+// Returns the path of the given file in the output directory, adding a number before the
+// extension if another output already uses that path.
 fn create_output_path(
     output_dir: &Path,
-    file_stem: &OsStr,
-    suffix: &str,
+    file_name: &OsStr,
     used: &mut HashSet<PathBuf>,
-    is_lut: bool,
 ) -> Option<PathBuf> {
+    let path = output_dir.join(file_name);
+    let file_stem = path.file_stem()?;
     for index in iter::once(None).chain((0..).map(Some)) {
-        let path = output_dir.join(Path::new(&format!(
-            "{}_{}{}.{}",
-            file_stem.to_string_lossy(),
-            suffix,
-            match index {
-                None => "".to_owned(),
-                Some(index) => format!("_{}", index),
-            },
-            if is_lut { "png" } else { "ktx2" },
-        )));
+        let mut candidate = path.clone();
+        if let Some(index) = index {
+            let mut numbered_file_stem = file_stem.to_owned();
+            numbered_file_stem.push(format!("_{}", index));
+            candidate.set_file_name(numbered_file_stem);
+            if let Some(extension) = path.extension() {
+                candidate.set_extension(extension);
+            }
+        }
 
-        if used.insert(path.clone()) {
-            return Some(path);
+        if used.insert(candidate.clone()) {
+            return Some(candidate);
         }
     }
 
@@ -793,6 +2112,18 @@ fn output_optional_numeric_value_ui(
     ui.end_row();
 }
 
+// Shows the problems with a field on the rows below it.
+fn field_issues_ui(ui: &mut Ui, issues: &[ValidationIssue], field: JobField) {
+    for issue in validation::field_issues(issues, field) {
+        ui.label("");
+        ui.colored_label(
+            Color32::RED,
+            format!("⚠ {}", issue.kind.to_localized_string()),
+        );
+        ui.end_row();
+    }
+}
+
 /// Returns true if the file changed or false otherwise.
 fn output_file_picker(
     ui: &mut Ui,
@@ -970,6 +2301,131 @@ impl Log for LogBuffer {
     fn flush(&self) {}
 }
 
+/// Returns how far along a job in progress is, from 0 to 1.
+#[allow(clippy::eq_op)]
+fn progress_fraction(output_progress: &OutputProgress) -> f32 {
+    let OutputProgress::InProgress {
+        input_reencoding_status,
+        outputs_finished,
+        output_count,
+        ref outputs,
+    } = *output_progress
+    else {
+        return 0.0;
+    };
+
+    let mut progress = match input_reencoding_status {
+        InputReencodingStatus::Loading => 0.0 / 3.0,
+        InputReencodingStatus::Resizing => 1.0 / 3.0,
+        InputReencodingStatus::Writing => 2.0 / 3.0,
+        InputReencodingStatus::Reencoded => 3.0 / 3.0,
+    };
+    progress += outputs_finished as f32;
+    if let Some((_, _, Some(sampler_progress))) = running_output(outputs) {
+        progress += sampler_progress.fraction();
+    }
+    progress / (output_count + 1) as f32
+}
+
+// Returns the index of the output that's running, how long it has been running, and how far the
+// sampler has got with it.
+fn running_output(outputs: &[OutputStatus]) -> Option<(usize, Duration, Option<SamplerProgress>)> {
+    outputs
+        .iter()
+        .enumerate()
+        .find_map(|(index, output)| match (&output.state, output.started) {
+            (OutputState::Running, Some(started)) => {
+                Some((index, started.elapsed(), output.sampler_progress))
+            }
+            _ => None,
+        })
+}
+
+// Shows one row of the results grid.
+fn output_result_ui(ui: &mut Ui, output_index: usize, output: &OutputStatus) {
+    ui.label(t!("output.header", index = output_index + 1));
+
+    match output.state {
+        OutputState::Pending => ui.label(t!("results.state.pending")),
+        OutputState::Running => ui.label(t!("results.state.running")),
+        OutputState::Done(_) => ui.label(t!("results.state.done")),
+        OutputState::Unchanged(_) => ui.label(t!("results.state.unchanged")),
+        OutputState::Failed => ui.colored_label(Color32::RED, t!("results.state.failed")),
+        OutputState::Skipped => ui.weak(t!("results.state.skipped")),
+    };
+
+    match output.elapsed {
+        Some(elapsed) => ui.label(format_duration(elapsed)),
+        None => ui.label(""),
+    };
+
+    let (OutputState::Done(ref result) | OutputState::Unchanged(ref result)) = output.state else {
+        return;
+    };
+
+    ui.label(format_file_size(result.file_size));
+
+    let mut settings = vec![
+        t!(
+            "results.resolution",
+            width = result.width,
+            height = result.height
+        ),
+        t!("results.mip.levels", count = result.mip_level_count),
+    ];
+    if let Some(sample_count) = result.sample_count {
+        settings.push(t!("results.samples", count = sample_count));
+    }
+    ui.label(settings.join(", "));
+
+    if ui
+        .small_button(t!("button.reveal"))
+        .on_hover_text(result.path.display().to_string())
+        .clicked()
+    {
+        if let Err(error) = reveal::reveal_in_file_manager(&result.path) {
+            show_error(&t!("results.reveal.failed", error = error));
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f32();
+    if seconds < 60.0 {
+        t!("duration.seconds", seconds = format!("{:.1}", seconds))
+    } else {
+        let seconds = duration.as_secs();
+        t!(
+            "duration.minutes",
+            minutes = seconds / 60,
+            seconds = seconds % 60
+        )
+    }
+}
+
+fn format_file_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return t!("results.size.bytes", count = size);
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn show_error(description: &str) {
+    MessageDialog::new()
+        .set_title(t!("app.title"))
+        .set_level(MessageLevel::Error)
+        .set_buttons(MessageButtons::Ok)
+        .set_description(description)
+        .show();
+}
+
 /// Lays out text with Markdown-like `code blocks`.
 fn layout_text_with_code(text: &str) -> LayoutJob {
     let mut code = false;
@@ -0,0 +1,352 @@
+// gltf-ibl-sampler-egui/src/manifest.rs
+//
+// Manifests, which are written next to each output and record what it was generated from, so that
+// outputs whose input and settings haven't changed can be skipped the next time the job runs, and
+// so that anyone can find out how an output was made and open the job that made it.
+
+use crate::generator::{ContainerFormat, Job, Output, OutputResult, Projection, TargetFormat};
+use crate::staging::Staging;
+use crate::{job_file, os_path};
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Added to the file name of an output's cubemap to get the name of its manifest.
+pub(crate) static MANIFEST_SUFFIX: &str = ".ibl.json";
+
+/// What does the sampling. Projections and encodings that the sampler can't do are done on the CPU
+/// by this app.
+static SAMPLER: &str = "glTF-IBL-Sampler (Vulkan)";
+
+/// What an output was generated from, and what was written.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    #[serde(flatten)]
+    pub(crate) key: BuildKey,
+    /// The whole job that the output was generated by, so that it can be opened again.
+    pub(crate) job: Job,
+    pub(crate) input: InputInfo,
+    pub(crate) backend: Backend,
+    /// When the output was generated, in RFC 3339 format.
+    pub(crate) generated_at: String,
+    /// Every file that was written for the output.
+    pub(crate) files: Vec<ManifestPath>,
+    /// What was written, including the settings that were chosen automatically.
+    pub(crate) result: OutputResult,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct InputInfo {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// The size that the input was scaled to before it was sampled.
+    pub(crate) sampled_width: u32,
+    pub(crate) sampled_height: u32,
+}
+
+/// How an output was generated: what sampled it, and what was done to the sampler's output
+/// afterwards.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Backend {
+    pub(crate) sampler: String,
+    /// True if the job ran in a worker process rather than in the app itself.
+    pub(crate) worker_process: bool,
+    /// The projection that the sampler's cubemap was converted to on the CPU, if it was.
+    pub(crate) cpu_reprojection: Option<Projection>,
+    /// How the output was encoded on the CPU, if the sampler didn't write it itself.
+    pub(crate) cpu_encoding: Option<CpuEncoding>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CpuEncoding {
+    pub(crate) encoder: CpuEncoder,
+    pub(crate) target_format: TargetFormat,
+    pub(crate) container_format: ContainerFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum CpuEncoder {
+    /// The ASTC HDR block encoder in `astc`.
+    Astc,
+    /// A plain conversion of the sampler's floating-point values to the target format.
+    Conversion,
+}
+
+/// Everything that the files of an output depend on. An output only has to be generated again if
+/// its key changes.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct BuildKey {
+    /// The version of the app that generated the output.
+    pub(crate) tool_version: String,
+    /// The SHA-256 hash of the input image, in hexadecimal, or empty if it couldn't be hashed.
+    pub(crate) input_hash: String,
+    /// The input is scaled down to this size before it's sampled, so it affects every output.
+    pub(crate) max_image_size: u32,
+    pub(crate) output: Output,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ManifestPath(#[serde(with = "os_path")] pub(crate) PathBuf);
+
+impl BuildKey {
+    pub(crate) fn new(job: &Job, input_hash: &str, output: &Output) -> BuildKey {
+        BuildKey {
+            tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+            input_hash: input_hash.to_owned(),
+            max_image_size: job.max_image_size,
+            output: output.clone(),
+        }
+    }
+}
+
+impl Backend {
+    /// Describes how `output` was generated. `written_by_sampler` is true if the sampler wrote the
+    /// output's file itself, rather than a floating-point cubemap that was converted on the CPU.
+    pub(crate) fn new(job: &Job, output: &Output, written_by_sampler: bool) -> Backend {
+        let encoder = if output.target_format.astc_block_size().is_some() {
+            CpuEncoder::Astc
+        } else {
+            CpuEncoder::Conversion
+        };
+        Backend {
+            sampler: SAMPLER.to_owned(),
+            worker_process: job.run_in_worker,
+            cpu_reprojection: (output.projection != Projection::Cubemap)
+                .then_some(output.projection),
+            cpu_encoding: (!written_by_sampler).then_some(CpuEncoding {
+                encoder,
+                target_format: output.target_format,
+                container_format: output.container_format,
+            }),
+        }
+    }
+}
+
+impl Manifest {
+    pub(crate) fn new(
+        key: BuildKey,
+        job: &Job,
+        input: InputInfo,
+        backend: Backend,
+        files: &[PathBuf],
+        result: OutputResult,
+    ) -> Manifest {
+        Manifest {
+            key,
+            job: job.clone(),
+            input,
+            backend,
+            generated_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            files: files.iter().cloned().map(ManifestPath).collect(),
+            result,
+        }
+    }
+}
+
+/// Returns the path of the manifest for the output whose cubemap is at `cubemap_path`.
+pub(crate) fn manifest_path(cubemap_path: &Path) -> PathBuf {
+    let mut path = OsString::from(cubemap_path.as_os_str());
+    path.push(MANIFEST_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Returns true if the file looks like the manifest of an output rather than a job file.
+pub(crate) fn is_manifest(path: &Path) -> bool {
+    path.file_name().is_some_and(|file_name| {
+        file_name
+            .to_string_lossy()
+            .to_lowercase()
+            .ends_with(MANIFEST_SUFFIX)
+    })
+}
+
+/// Reads the job that generated an output from its manifest.
+pub(crate) fn load_job(path: &Path) -> Result<Job, Error> {
+    let manifest: Manifest = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut job = manifest.job;
+    job_file::migrate(&mut job);
+    Ok(job)
+}
+
+/// Hashes the contents of a file with SHA-256.
+pub(crate) fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Returns what was written for the output the last time that it was generated, if it was
+/// generated from the same key and all of its files still exist.
+pub(crate) fn up_to_date(key: &BuildKey) -> Option<OutputResult> {
+    let text = fs::read_to_string(manifest_path(&key.output.out_cubemap.path)).ok()?;
+    let manifest: Manifest = serde_json::from_str(&text).ok()?;
+    (manifest.key == *key && manifest.files.iter().all(|file| file.0.is_file()))
+        .then_some(manifest.result)
+}
+
+/// Writes the manifest for an output to `staging`, so that it's replaced along with the output.
+pub(crate) fn write(manifest: &Manifest, staging: &mut Staging) -> Result<(), Error> {
+    let path = manifest_path(&manifest.key.output.out_cubemap.path);
+    fs::write(
+        staging.stage(&path)?,
+        serde_json::to_string_pretty(manifest)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::OutputPath;
+    use tempfile::TempDir;
+
+    // Returns a job with one output in a new directory.
+    fn job() -> (TempDir, Job) {
+        let dir = tempfile::tempdir().unwrap();
+        let output = Output {
+            out_cubemap: OutputPath {
+                path: dir.path().join("studio.ktx2"),
+                automatic_filename: false,
+            },
+            ..Output::default()
+        };
+        let job = Job {
+            input_path: dir.path().join("studio.hdr"),
+            outputs: vec![output],
+            ..Job::default()
+        };
+        (dir, job)
+    }
+
+    // Writes the output's cubemap and its manifest, as if the output had just been generated.
+    fn generate(job: &Job, key: BuildKey) {
+        let path = &job.outputs[0].out_cubemap.path;
+        let result = OutputResult {
+            path: path.clone(),
+            file_size: 4,
+            width: 16,
+            height: 16,
+            mip_level_count: 5,
+            sample_count: None,
+            cubemap_resolution: 16,
+        };
+        let input = InputInfo {
+            width: 64,
+            height: 32,
+            sampled_width: 64,
+            sampled_height: 32,
+        };
+        let backend = Backend::new(job, &job.outputs[0], true);
+        let manifest = Manifest::new(key, job, input, backend, std::slice::from_ref(path), result);
+
+        let mut staging = Staging::new();
+        fs::write(staging.stage(path).unwrap(), "ktx2").unwrap();
+        write(&manifest, &mut staging).unwrap();
+        staging.commit().unwrap();
+    }
+
+    #[test]
+    fn keys_change_with_the_input_and_settings() {
+        let (_dir, job) = job();
+        let key = BuildKey::new(&job, "abc", &job.outputs[0]);
+        assert!(key == BuildKey::new(&job, "abc", &job.outputs[0]));
+        assert!(key != BuildKey::new(&job, "abd", &job.outputs[0]));
+
+        let mut changed_job = job.clone();
+        changed_job.max_image_size /= 2;
+        assert!(key != BuildKey::new(&changed_job, "abc", &job.outputs[0]));
+
+        let mut changed_output = job.outputs[0].clone();
+        changed_output.lod_bias = 1.0;
+        assert!(key != BuildKey::new(&job, "abc", &changed_output));
+
+        // Settings that only affect other parts of the job don't matter.
+        let mut changed_job = job.clone();
+        changed_job.transactional = !changed_job.transactional;
+        assert!(key == BuildKey::new(&changed_job, "abc", &job.outputs[0]));
+    }
+
+    #[test]
+    fn backends_describe_the_work_done_on_the_cpu() {
+        let (_dir, mut job) = job();
+        let backend = Backend::new(&job, &job.outputs[0], true);
+        assert!(!backend.worker_process);
+        assert!(backend.cpu_reprojection.is_none());
+        assert!(backend.cpu_encoding.is_none());
+
+        job.run_in_worker = true;
+        job.outputs[0].projection = Projection::Octahedral;
+        job.outputs[0].target_format = TargetFormat::Astc6x6Sfloat;
+        let backend = Backend::new(&job, &job.outputs[0], false);
+        assert!(backend.worker_process);
+        assert!(backend.cpu_reprojection == Some(Projection::Octahedral));
+        let cpu_encoding = backend.cpu_encoding.unwrap();
+        assert!(cpu_encoding.encoder == CpuEncoder::Astc);
+        assert!(cpu_encoding.target_format == TargetFormat::Astc6x6Sfloat);
+
+        job.outputs[0].projection = Projection::Cubemap;
+        job.outputs[0].target_format = TargetFormat::R9G9B9E5Ufloat;
+        job.outputs[0].container_format = ContainerFormat::Dds;
+        let backend = Backend::new(&job, &job.outputs[0], false);
+        assert!(backend.cpu_reprojection.is_none());
+        assert!(backend.cpu_encoding.unwrap().encoder == CpuEncoder::Conversion);
+    }
+
+    #[test]
+    fn outputs_are_up_to_date_until_their_key_changes() {
+        let (_dir, job) = job();
+        let key = BuildKey::new(&job, "abc", &job.outputs[0]);
+        assert!(up_to_date(&key).is_none());
+
+        generate(&job, key.clone());
+        let result = up_to_date(&key).unwrap();
+        assert_eq!(result.path, job.outputs[0].out_cubemap.path);
+        assert_eq!(result.mip_level_count, 5);
+
+        assert!(up_to_date(&BuildKey::new(&job, "abd", &job.outputs[0])).is_none());
+        let older_key = BuildKey {
+            tool_version: "0.0.0".to_owned(),
+            ..key
+        };
+        assert!(up_to_date(&older_key).is_none());
+    }
+
+    #[test]
+    fn outputs_with_missing_files_are_not_up_to_date() {
+        let (_dir, job) = job();
+        let key = BuildKey::new(&job, "abc", &job.outputs[0]);
+        generate(&job, key.clone());
+        fs::remove_file(&job.outputs[0].out_cubemap.path).unwrap();
+        assert!(up_to_date(&key).is_none());
+    }
+
+    #[test]
+    fn manifests_can_be_told_apart_from_jobs() {
+        let (_dir, job) = job();
+        generate(&job, BuildKey::new(&job, "abc", &job.outputs[0]));
+        let path = manifest_path(&job.outputs[0].out_cubemap.path);
+        assert!(is_manifest(&path));
+        assert!(!is_manifest(&path.with_file_name("studio.json")));
+        assert!(load_job(&path).unwrap() == job);
+    }
+
+    #[test]
+    fn files_are_hashed_with_sha256() {
+        let (dir, _job) = job();
+        let path = dir.path().join("input.txt");
+        fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            hash_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,58 @@
+// gltf-ibl-sampler-egui/src/openexr.rs
+
+use crate::texture::Texture;
+use anyhow::Error;
+use exr::prelude::{
+    write_rgba_file, Encoding, Image, ImageAttributes, IntegerBounds, Layer, LayerAttributes,
+    SpecificChannels, Vec2, WritableImage,
+};
+use std::path::Path;
+
+static FACE_NAMES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+/// Writes every face of every mip level as a separate part of a single multi-part EXR file.
+///
+/// Parts are named after the face and level, for example `px_mip0`, or just after the level, for
+/// example `mip0`, for 2D textures.
+pub(crate) fn write_multi_part(path: &Path, texture: &Texture) -> Result<(), Error> {
+    let mut layers = vec![];
+    for (level_index, faces) in texture.levels.iter().enumerate() {
+        let (width, height) = texture.level_size(level_index);
+        let (width, height) = (width as usize, height as usize);
+        for (face_index, face) in faces.iter().enumerate() {
+            let name = if texture.is_cubemap() {
+                format!("{}_mip{}", FACE_NAMES[face_index], level_index)
+            } else {
+                format!("mip{}", level_index)
+            };
+            let channels = SpecificChannels::rgba(move |Vec2(x, y): Vec2<usize>| {
+                let pixel = &face[(y * width + x) * 4..][..4];
+                (pixel[0], pixel[1], pixel[2], pixel[3])
+            });
+            layers.push(Layer::new(
+                (width, height),
+                LayerAttributes::named(name.as_str()),
+                Encoding::FAST_LOSSLESS,
+                channels,
+            ));
+        }
+    }
+
+    let bounds = IntegerBounds::from_dimensions((texture.width as usize, texture.height as usize));
+    Image::from_layers(ImageAttributes::new(bounds), layers)
+        .write()
+        .to_file(path)?;
+    Ok(())
+}
+
+/// Writes a single mip level to its own EXR file, with cubemaps laid out in a horizontal cross.
+///
+/// Levels are usually written next to each other, as named by [`crate::texture::mip_level_path`].
+pub(crate) fn write_level(path: &Path, texture: &Texture, level_index: usize) -> Result<(), Error> {
+    let (width, height, pixels) = texture.level_image(level_index);
+    write_rgba_file(path, width as usize, height as usize, |x, y| {
+        let pixel = &pixels[(y * width as usize + x) * 4..][..4];
+        (pixel[0], pixel[1], pixel[2], pixel[3])
+    })?;
+    Ok(())
+}
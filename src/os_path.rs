@@ -0,0 +1,188 @@
+// gltf-ibl-sampler-egui/src/os_path.rs
+//
+// Handling of paths that aren't valid Unicode.
+//
+// The native sampler takes paths as C strings, and serde only knows how to write paths that are
+// valid UTF-8, so both need help with paths that the operating system allows but that can't be
+// written as UTF-8.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ffi::{CString, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+#[cfg(windows)]
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+// How a path is stored when it isn't valid UTF-8: its raw bytes on Unix, or its UTF-16 code units
+// on Windows.
+#[cfg(unix)]
+type RawPath = Vec<u8>;
+#[cfg(windows)]
+type RawPath = Vec<u16>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerializedPath {
+    Utf8(String),
+    Raw(RawPath),
+}
+
+/// Makes the C runtime interpret the paths that we pass to the sampler as UTF-8.
+///
+/// On Windows, the C runtime otherwise uses the system's legacy code page, which can't represent
+/// most Unicode characters. Other platforms pass the bytes of paths through unchanged.
+pub(crate) fn use_utf8_in_c_runtime() {
+    #[cfg(windows)]
+    unsafe {
+        // Only the character type category, so that numbers are still formatted the same way.
+        libc::setlocale(libc::LC_CTYPE, b".UTF8\0".as_ptr() as *const _);
+    }
+}
+
+/// Converts a path to a C string that the sampler can open.
+///
+/// On Windows, this fails for paths that aren't valid Unicode, since the sampler only takes
+/// narrow strings. Jobs are checked for such paths before they run; see [`sampler_can_open`].
+pub(crate) fn to_c_string(path: &Path) -> io::Result<CString> {
+    #[cfg(unix)]
+    let bytes = path.as_os_str().as_bytes().to_owned();
+    // Windows paths can contain unpaired surrogates, which no narrow encoding can represent.
+    #[cfg(windows)]
+    let bytes = path
+        .to_str()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The path contains characters that the sampler can't open",
+            )
+        })?
+        .as_bytes()
+        .to_owned();
+
+    CString::new(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+}
+
+/// Returns false if [`to_c_string`] can't convert the path.
+pub(crate) fn sampler_can_open(path: &Path) -> bool {
+    cfg!(not(windows)) || path.to_str().is_some()
+}
+
+/// Serializes a path as a string if it's valid UTF-8 and as its raw representation otherwise.
+///
+/// Use with `#[serde(with = "os_path")]`.
+pub(crate) fn serialize<S>(path: &Path, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match path.to_str() {
+        Some(path) => SerializedPath::Utf8(path.to_owned()),
+        #[cfg(unix)]
+        None => SerializedPath::Raw(path.as_os_str().as_bytes().to_owned()),
+        #[cfg(windows)]
+        None => SerializedPath::Raw(path.as_os_str().encode_wide().collect()),
+    }
+    .serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match SerializedPath::deserialize(deserializer)? {
+        SerializedPath::Utf8(path) => PathBuf::from(path),
+        #[cfg(unix)]
+        SerializedPath::Raw(path) => PathBuf::from(OsString::from_vec(path)),
+        #[cfg(windows)]
+        SerializedPath::Raw(path) => PathBuf::from(OsString::from_wide(&path)),
+    })
+}
+
+/// Like the parent module, for optional paths.
+pub(crate) mod option {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::path::{Path, PathBuf};
+
+    #[derive(Serialize, Deserialize)]
+    struct SerializedPath(#[serde(with = "super")] PathBuf);
+
+    pub(crate) fn serialize<S>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        path.as_deref()
+            .map(|path: &Path| SerializedPath(path.to_owned()))
+            .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<SerializedPath>::deserialize(deserializer)?.map(|path| path.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Paths {
+        #[serde(with = "super")]
+        path: PathBuf,
+        #[serde(with = "super::option")]
+        optional_path: Option<PathBuf>,
+    }
+
+    // Returns a path that isn't valid Unicode.
+    fn non_unicode_path() -> PathBuf {
+        #[cfg(unix)]
+        let path = OsString::from_vec(b"studio\xff.hdr".to_vec());
+        #[cfg(windows)]
+        let path = OsString::from_wide(&[0x73, 0xd800, 0x2e, 0x68, 0x64, 0x72]);
+        PathBuf::from(path)
+    }
+
+    #[test]
+    fn unicode_paths_are_written_as_strings() {
+        let paths = Paths {
+            path: PathBuf::from("ibl/studio.ktx2"),
+            optional_path: None,
+        };
+        let json = serde_json::to_string(&paths).unwrap();
+        assert_eq!(json, r#"{"path":"ibl/studio.ktx2","optional_path":null}"#);
+        assert_eq!(serde_json::from_str::<Paths>(&json).unwrap(), paths);
+    }
+
+    #[test]
+    fn non_unicode_paths_round_trip() {
+        let paths = Paths {
+            path: non_unicode_path(),
+            optional_path: Some(non_unicode_path()),
+        };
+        assert!(paths.path.to_str().is_none());
+
+        let json = serde_json::to_string(&paths).unwrap();
+        assert_eq!(serde_json::from_str::<Paths>(&json).unwrap(), paths);
+        let ron = ron::to_string(&paths).unwrap();
+        assert_eq!(ron::from_str::<Paths>(&ron).unwrap(), paths);
+    }
+
+    #[test]
+    fn c_strings_keep_every_byte_of_the_path() {
+        let path = Path::new("ibl/studio \u{e9}.hdr");
+        assert_eq!(
+            to_c_string(path).unwrap().as_bytes(),
+            "ibl/studio \u{e9}.hdr".as_bytes()
+        );
+        assert!(to_c_string(Path::new("studio\0.hdr")).is_err());
+
+        let path = non_unicode_path();
+        assert_eq!(sampler_can_open(&path), to_c_string(&path).is_ok());
+        #[cfg(unix)]
+        assert_eq!(to_c_string(&path).unwrap().as_bytes(), b"studio\xff.hdr");
+    }
+}
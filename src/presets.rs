@@ -0,0 +1,196 @@
+// gltf-ibl-sampler-egui/src/presets.rs
+
+use crate::bevy;
+use crate::generator::{
+    ContainerFormat, Distribution, FilterSettings, Output, Projection, TargetFormat,
+};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+
+/// A named list of outputs that can replace the outputs of the current job.
+///
+/// Output paths aren't part of a preset; they're always chosen automatically when a preset is
+/// applied.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Preset {
+    pub(crate) name: String,
+    pub(crate) outputs: Vec<Output>,
+}
+
+impl Preset {
+    /// Creates a preset from the outputs of a job.
+    pub(crate) fn new(name: String, outputs: &[Output]) -> Preset {
+        let mut outputs = outputs.to_vec();
+        for output in &mut outputs {
+            output.use_automatic_paths();
+        }
+        Preset { name, outputs }
+    }
+}
+
+/// Returns the presets for the engines that we know about.
+pub(crate) fn built_in_presets() -> Vec<Preset> {
+    vec![
+        Preset {
+            name: "Bevy".to_owned(),
+            outputs: bevy::preset_outputs(),
+        },
+        // three.js prefilters environment maps itself with `PMREMGenerator`, so it only needs
+        // the unfiltered base level.
+        Preset {
+            name: "three.js".to_owned(),
+            outputs: vec![Output {
+                mip_level_count: Some(1),
+                ..output(
+                    None,
+                    Some(1024),
+                    "{stem}_envmap_{resolution}.{ext}",
+                    "envmap",
+                )
+            }],
+        },
+        // Godot computes its radiance maps itself from the panorama of a `PanoramaSkyMaterial`.
+        Preset {
+            name: "Godot".to_owned(),
+            outputs: vec![Output {
+                projection: Projection::Equirectangular,
+                container_format: ContainerFormat::ExrPerLevel,
+                mip_level_count: Some(1),
+                ..output(None, Some(1024), "{stem}_panorama.{ext}", "panorama")
+            }],
+        },
+        // These follow the file names and sizes that Filament's `cmgen` writes. Filament has its
+        // own DFG lookup table, so the lookup tables that the sampler writes aren't needed.
+        Preset {
+            name: "Filament".to_owned(),
+            outputs: vec![
+                output(None, Some(1024), FILAMENT_TEMPLATE, "skybox"),
+                Output {
+                    mip_level_count: Some(1),
+                    ..output(
+                        Some(Distribution::Lambertian),
+                        Some(32),
+                        FILAMENT_TEMPLATE,
+                        "irradiance",
+                    )
+                },
+                output(Some(Distribution::Ggx), Some(256), FILAMENT_TEMPLATE, "ibl"),
+            ],
+        },
+        // These match the environment maps that ship with the Khronos glTF Sample Viewer. Filtered
+        // maps go in a directory named after their distribution, next to their lookup tables.
+        Preset {
+            name: t!("preset.gltf.viewer"),
+            outputs: vec![
+                output(None, Some(1024), "{stem}/{kind}.{ext}", "environment"),
+                output(
+                    Some(Distribution::Lambertian),
+                    Some(64),
+                    GLTF_VIEWER_TEMPLATE,
+                    "diffuse",
+                ),
+                output(
+                    Some(Distribution::Ggx),
+                    Some(256),
+                    GLTF_VIEWER_TEMPLATE,
+                    "specular",
+                ),
+                output(
+                    Some(Distribution::Charlie),
+                    Some(256),
+                    GLTF_VIEWER_TEMPLATE,
+                    "sheen",
+                ),
+            ],
+        },
+    ]
+}
+
+// `cmgen` writes `<name>/<name>_ibl.ktx` and `<name>/<name>_skybox.ktx`.
+static FILAMENT_TEMPLATE: &str = "{stem}/{stem}_{kind}.{ext}";
+
+static GLTF_VIEWER_TEMPLATE: &str = "{stem}/{distribution}/{kind}.{ext}";
+
+// Returns a half-float KTX2 cubemap output with the given distribution and resolution, whose files
+// are named by `filename_template` with `kind` as the kind.
+fn output(
+    distribution: Option<Distribution>,
+    cubemap_resolution: Option<u32>,
+    filename_template: &str,
+    kind: &str,
+) -> Output {
+    let mut output = Output::default_for_index(0);
+    output.target_format = TargetFormat::R16G16B16A16Sfloat;
+    output.cubemap_resolution = cubemap_resolution;
+    output.filename_template = filename_template.to_owned();
+    output.file_suffix = Some(kind.to_owned());
+    output.filter_settings = distribution.map(|distribution| FilterSettings {
+        distribution,
+        ..FilterSettings::default_for_index(0)
+    });
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filename::{self, TemplateContext};
+    use std::collections::HashSet;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn built_in_presets_name_every_file_differently() {
+        let context = TemplateContext::new(OsStr::new("studio"));
+        for preset in built_in_presets() {
+            let mut file_names = HashSet::new();
+            for output in &preset.outputs {
+                let kind = filename::kind(output);
+                let extension = output.container_format.extension();
+                let file_name = filename::expand_template(output, &context, kind, extension);
+                let lut_file_name =
+                    filename::expand_template(output, &context, &format!("{}_lut", kind), "png");
+                assert!(file_names.insert(file_name), "{}", preset.name);
+                if output.filter_settings.is_some() {
+                    assert!(file_names.insert(lut_file_name), "{}", preset.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn engine_file_names_follow_their_conventions() {
+        let context = TemplateContext::new(OsStr::new("studio"));
+        let file_names = |name: &str| -> Vec<_> {
+            let preset = built_in_presets()
+                .into_iter()
+                .find(|preset| preset.name == name)
+                .unwrap();
+            preset
+                .outputs
+                .iter()
+                .map(|output| {
+                    let extension = output.container_format.extension();
+                    filename::expand_template(output, &context, filename::kind(output), extension)
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            file_names("Bevy"),
+            [
+                "studio_cubemap_rgb9e5.ktx2",
+                "studio_diffuse_rgb9e5.ktx2",
+                "studio_specular_rgb9e5.ktx2"
+            ]
+        );
+        assert_eq!(
+            file_names("Filament"),
+            [
+                "studio/studio_skybox.ktx2",
+                "studio/studio_irradiance.ktx2",
+                "studio/studio_ibl.ktx2"
+            ]
+        );
+        assert_eq!(file_names("Godot"), ["studio_panorama.exr"]);
+    }
+}
@@ -0,0 +1,313 @@
+// gltf-ibl-sampler-egui/src/projection.rs
+
+use crate::texture::Texture;
+use std::f32::consts::PI;
+use std::thread;
+
+/// Reprojects every mip level of a cubemap to the equirectangular projection.
+///
+/// Each level is twice as wide as it is tall and has four times the width of the corresponding
+/// cubemap face, so each mip level keeps the roughness of the cubemap level it came from. The
+/// top row is straight up (+Y), and the center of the image faces +X, like the input panorama.
+pub(crate) fn equirectangular(cubemap: &Texture) -> Texture {
+    let width = cubemap.width * 4;
+    let height = cubemap.height * 2;
+    let levels = (0..cubemap.levels.len())
+        .map(|level| {
+            let level_width = (width >> level).max(1);
+            let level_height = (height >> level).max(1);
+            reproject_level(level_width, level_height, |u, v| {
+                let (phi, theta) = ((u - 0.5) * 2.0 * PI, v * PI);
+                let direction = [
+                    phi.cos() * theta.sin(),
+                    theta.cos(),
+                    phi.sin() * theta.sin(),
+                ];
+                sample_cubemap(cubemap, level, direction)
+            })
+        })
+        .collect();
+
+    Texture {
+        width,
+        height,
+        levels,
+    }
+}
+
+/// Reprojects every mip level of a cubemap to the octahedral mapping, with +Y at the center.
+///
+/// `resolution` is the width and height of the first level, including `border` texels of padding
+/// on each side. The padding repeats the texels across each edge of the octahedron so that
+/// bilinear filtering is seamless. As with the equirectangular projection, each mip level keeps
+/// the roughness of the cubemap level it came from. If the resolution is too small for all of the
+/// cubemap's levels, the levels below 1×1 are dropped.
+pub(crate) fn octahedral(cubemap: &Texture, resolution: u32, border: u32) -> Texture {
+    let resolution = resolution.max(1);
+    let level_count = cubemap.levels.len().min(resolution.ilog2() as usize + 1);
+    let levels = (0..level_count)
+        .map(|level| {
+            let size = (resolution >> level).max(1);
+            // Keep the padding no wider than the interior, so that it can be mirrored.
+            let border = border.min(size / 3);
+            let interior = (size - border * 2) as i64;
+            reproject_level(size, size, |u, v| {
+                let x = (u * size as f32) as i64 - border as i64;
+                let y = (v * size as f32) as i64 - border as i64;
+                let (x, y) = wrap_octahedral_texel(x, y, interior);
+                let u = (x as f32 + 0.5) / interior as f32 * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / interior as f32 * 2.0 - 1.0;
+                sample_cubemap(cubemap, level, octahedral_direction(u, v))
+            })
+        })
+        .collect();
+
+    Texture {
+        width: resolution,
+        height: resolution,
+        levels,
+    }
+}
+
+// Returns the (unnormalized) direction through the given point on an octahedral map, where `u`
+// and `v` range from -1 to 1.
+fn octahedral_direction(u: f32, v: f32) -> [f32; 3] {
+    let mut direction = [u, 1.0 - u.abs() - v.abs(), v];
+    if direction[1] < 0.0 {
+        direction[0] = (1.0 - v.abs()) * u.signum();
+        direction[2] = (1.0 - u.abs()) * v.signum();
+    }
+    direction
+}
+
+// Maps a texel outside an octahedral map to the texel it borders on the other side of the
+// octahedron. Each edge of the map is mirrored about its midpoint.
+fn wrap_octahedral_texel(mut x: i64, mut y: i64, size: i64) -> (i64, i64) {
+    if x < 0 {
+        (x, y) = (-1 - x, size - 1 - y);
+    } else if x >= size {
+        (x, y) = (2 * size - 1 - x, size - 1 - y);
+    }
+    if y < 0 {
+        (x, y) = (size - 1 - x, -1 - y);
+    } else if y >= size {
+        (x, y) = (size - 1 - x, 2 * size - 1 - y);
+    }
+    (x, y)
+}
+
+// Evaluates `sample` at the center of every texel of a single-face level, splitting the rows
+// across threads.
+fn reproject_level<F>(width: u32, height: u32, sample: F) -> Vec<Vec<f32>>
+where
+    F: Fn(f32, f32) -> [f32; 4] + Sync,
+{
+    let mut pixels = vec![0.0; width as usize * height as usize * 4];
+    let thread_count = thread::available_parallelism().map_or(1, |count| count.get());
+    let rows_per_thread = (height as usize).div_ceil(thread_count);
+    thread::scope(|scope| {
+        for (chunk_index, chunk) in pixels
+            .chunks_mut(rows_per_thread * width as usize * 4)
+            .enumerate()
+        {
+            let sample = &sample;
+            scope.spawn(move || {
+                for (row_index, row) in chunk.chunks_exact_mut(width as usize * 4).enumerate() {
+                    let y = chunk_index * rows_per_thread + row_index;
+                    let v = (y as f32 + 0.5) / height as f32;
+                    for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                        let u = (x as f32 + 0.5) / width as f32;
+                        pixel.copy_from_slice(&sample(u, v));
+                    }
+                }
+            });
+        }
+    });
+    vec![pixels]
+}
+
+/// Bilinearly samples the given mip level of a cubemap in the given direction.
+///
+/// Face selection follows the Vulkan specification. Samples are clamped to the edges of the
+/// selected face rather than filtered across neighboring faces.
+pub(crate) fn sample_cubemap(cubemap: &Texture, level: usize, direction: [f32; 3]) -> [f32; 4] {
+    let [x, y, z] = direction;
+    let (face, s, t, major) = if x.abs() >= y.abs() && x.abs() >= z.abs() {
+        if x >= 0.0 {
+            (0, -z, -y, x.abs())
+        } else {
+            (1, z, -y, x.abs())
+        }
+    } else if y.abs() >= z.abs() {
+        if y >= 0.0 {
+            (2, x, z, y.abs())
+        } else {
+            (3, x, -z, y.abs())
+        }
+    } else if z >= 0.0 {
+        (4, x, -y, z.abs())
+    } else {
+        (5, -x, -y, z.abs())
+    };
+
+    let (size, _) = cubemap.level_size(level);
+    let pixels = &cubemap.levels[level][face];
+    let s = ((s / major + 1.0) * 0.5 * size as f32 - 0.5).clamp(0.0, (size - 1) as f32);
+    let t = ((t / major + 1.0) * 0.5 * size as f32 - 0.5).clamp(0.0, (size - 1) as f32);
+    let (x0, y0) = (s.floor() as usize, t.floor() as usize);
+    let (x1, y1) = (
+        (x0 + 1).min(size as usize - 1),
+        (y0 + 1).min(size as usize - 1),
+    );
+    let (fx, fy) = (s.fract(), t.fract());
+
+    let texel = |x: usize, y: usize| &pixels[(y * size as usize + x) * 4..][..4];
+    let mut result = [0.0; 4];
+    for (channel, value) in result.iter_mut().enumerate() {
+        let top = texel(x0, y0)[channel] * (1.0 - fx) + texel(x1, y0)[channel] * fx;
+        let bottom = texel(x0, y1)[channel] * (1.0 - fx) + texel(x1, y1)[channel] * fx;
+        *value = top * (1.0 - fy) + bottom * fy;
+    }
+    result
+}
+
+/// Returns the (unnormalized) direction through the given point on a cubemap face, where `s`
+/// and `t` range from -1 to 1. This is the inverse of the face selection in [`sample_cubemap`].
+pub(crate) fn face_direction(face: usize, s: f32, t: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -t, -s],
+        1 => [-1.0, -t, s],
+        2 => [s, 1.0, t],
+        3 => [s, -1.0, -t],
+        4 => [s, -t, 1.0],
+        _ => [-s, -t, -1.0],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: i64 = 8;
+
+    // Returns the normalized direction through the center of a texel of an octahedral map.
+    fn texel_direction(x: i64, y: i64) -> [f32; 3] {
+        let u = (x as f32 + 0.5) / SIZE as f32 * 2.0 - 1.0;
+        let v = (y as f32 + 0.5) / SIZE as f32 * 2.0 - 1.0;
+        let direction = octahedral_direction(u, v);
+        let length = direction
+            .iter()
+            .map(|value| value * value)
+            .sum::<f32>()
+            .sqrt();
+        direction.map(|value| value / length)
+    }
+
+    fn angle_between(a: [f32; 3], b: [f32; 3]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+        dot.clamp(-1.0, 1.0).acos()
+    }
+
+    #[test]
+    fn interior_texels_are_not_wrapped() {
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                assert_eq!(wrap_octahedral_texel(x, y, SIZE), (x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn edges_are_mirrored_about_their_midpoints() {
+        assert_eq!(wrap_octahedral_texel(-1, 0, SIZE), (0, 7));
+        assert_eq!(wrap_octahedral_texel(-2, 5, SIZE), (1, 2));
+        assert_eq!(wrap_octahedral_texel(8, 2, SIZE), (7, 5));
+        assert_eq!(wrap_octahedral_texel(3, -1, SIZE), (4, 0));
+        assert_eq!(wrap_octahedral_texel(3, 9, SIZE), (4, 6));
+    }
+
+    #[test]
+    fn corners_wrap_to_the_opposite_corner() {
+        assert_eq!(wrap_octahedral_texel(-1, -1, SIZE), (7, 7));
+        assert_eq!(wrap_octahedral_texel(8, -1, SIZE), (0, 7));
+        assert_eq!(wrap_octahedral_texel(-1, 8, SIZE), (7, 0));
+        assert_eq!(wrap_octahedral_texel(8, 8, SIZE), (0, 0));
+    }
+
+    #[test]
+    fn border_texels_continue_across_the_edge() {
+        // Each texel just outside an edge should be a different texel from the one just inside
+        // it, but next to it on the sphere, as if the octahedron were folded back up. Texels are
+        // up to about twice as far apart near the corners of the map.
+        let max_angle = 2.0 * PI / SIZE as f32;
+        for i in 0..SIZE {
+            for (outside, inside) in [
+                ((-1, i), (0, i)),
+                ((SIZE, i), (SIZE - 1, i)),
+                ((i, -1), (i, 0)),
+                ((i, SIZE), (i, SIZE - 1)),
+            ] {
+                let (x, y) = wrap_octahedral_texel(outside.0, outside.1, SIZE);
+                let angle =
+                    angle_between(texel_direction(x, y), texel_direction(inside.0, inside.1));
+                assert_ne!((x, y), inside);
+                assert!(
+                    angle <= max_angle,
+                    "{:?} wrapped to {:?}, which is {} radians from {:?}",
+                    outside,
+                    (x, y),
+                    angle,
+                    inside
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn small_resolutions_drop_the_smallest_levels() {
+        let cubemap = Texture {
+            width: 16,
+            height: 16,
+            levels: (0..5)
+                .map(|level| vec![vec![1.0; (16 >> level) * (16 >> level) * 4]; 6])
+                .collect(),
+        };
+        let map = octahedral(&cubemap, 6, 1);
+        assert_eq!((map.width, map.height), (6, 6));
+        assert_eq!(map.levels.len(), 3);
+        for (level, faces) in map.levels.iter().enumerate() {
+            let (width, height) = map.level_size(level);
+            assert_eq!(faces[0].len(), width as usize * height as usize * 4);
+        }
+
+        assert_eq!(octahedral(&cubemap, 64, 1).levels.len(), 5);
+    }
+
+    #[test]
+    fn padding_repeats_the_interior() {
+        // A cubemap whose texels all have different values.
+        let face_size = 4;
+        let cubemap = Texture {
+            width: face_size,
+            height: face_size,
+            levels: vec![(0..6)
+                .map(|face| {
+                    (0..face_size * face_size * 4)
+                        .map(|index| (face * 100 + index) as f32)
+                        .collect()
+                })
+                .collect()],
+        };
+        let (size, border) = (SIZE + 4, 2);
+        let map = octahedral(&cubemap, size as u32, border as u32);
+        let pixels = &map.levels[0][0];
+        let pixel = |x: i64, y: i64| &pixels[((y * size + x) * 4) as usize..][..4];
+
+        for y in 0..size {
+            for x in 0..size {
+                let (wrapped_x, wrapped_y) = wrap_octahedral_texel(x - border, y - border, SIZE);
+                assert_eq!(pixel(x, y), pixel(wrapped_x + border, wrapped_y + border));
+            }
+        }
+    }
+}
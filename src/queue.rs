@@ -0,0 +1,208 @@
+// gltf-ibl-sampler-egui/src/queue.rs
+
+use crate::generator::{Job, JobError, OutputProgress};
+use crate::job_file;
+use serde::{Deserialize, Serialize};
+
+/// Jobs that run one after another in the background while the user edits other jobs.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct JobQueue {
+    pub(crate) jobs: Vec<QueuedJob>,
+    /// If true, no more jobs are started until the queue is resumed. A job that's already running
+    /// still finishes.
+    pub(crate) paused: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct QueuedJob {
+    pub(crate) job: Job,
+    pub(crate) state: QueuedJobState,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum QueuedJobState {
+    Waiting,
+    Running,
+    Done,
+    Failed(JobError),
+}
+
+impl JobQueue {
+    /// Decodes a queue that was saved when the app last quit.
+    ///
+    /// Jobs that were running then are started again from the beginning, and jobs saved by older
+    /// versions of the app are upgraded.
+    pub(crate) fn restore(encoded_queue: &str) -> Result<JobQueue, ron::Error> {
+        let mut queue: JobQueue = ron::from_str(encoded_queue)?;
+        for queued_job in &mut queue.jobs {
+            job_file::migrate(&mut queued_job.job);
+            if matches!(queued_job.state, QueuedJobState::Running) {
+                queued_job.state = QueuedJobState::Waiting;
+            }
+        }
+        Ok(queue)
+    }
+
+    pub(crate) fn add(&mut self, job: Job) {
+        self.jobs.push(QueuedJob {
+            job,
+            state: QueuedJobState::Waiting,
+        });
+    }
+
+    /// Records how the running job ended, if it has.
+    pub(crate) fn finish(&mut self, progress: &OutputProgress) {
+        let Some(queued_job) = self
+            .jobs
+            .iter_mut()
+            .find(|queued_job| matches!(queued_job.state, QueuedJobState::Running))
+        else {
+            return;
+        };
+
+        match *progress {
+            OutputProgress::Succeeded { .. } => queued_job.state = QueuedJobState::Done,
+            OutputProgress::Failed { ref error, .. } => {
+                queued_job.state = QueuedJobState::Failed(error.clone());
+            }
+            OutputProgress::NotStartedYet | OutputProgress::InProgress { .. } => {}
+        }
+    }
+
+    /// Marks the first waiting job as running and returns it, unless the queue is paused.
+    pub(crate) fn start_next(&mut self) -> Option<&Job> {
+        if self.paused {
+            return None;
+        }
+        let queued_job = self
+            .jobs
+            .iter_mut()
+            .find(|queued_job| matches!(queued_job.state, QueuedJobState::Waiting))?;
+        queued_job.state = QueuedJobState::Running;
+        Some(&queued_job.job)
+    }
+
+    /// Moves the job at index `from` so that it ends up at index `to`.
+    pub(crate) fn move_job(&mut self, from: usize, to: usize) {
+        let queued_job = self.jobs.remove(from);
+        self.jobs.insert(to.min(self.jobs.len()), queued_job);
+    }
+
+    /// Runs a job that failed or finished again.
+    pub(crate) fn retry(&mut self, index: usize) {
+        self.jobs[index].state = QueuedJobState::Waiting;
+    }
+
+    /// Removes every job that has finished successfully.
+    pub(crate) fn clear_done(&mut self) {
+        self.jobs
+            .retain(|queued_job| !matches!(queued_job.state, QueuedJobState::Done));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::OutputError;
+
+    // Returns a job that can be told apart from the others by its maximum image size.
+    fn job(id: u32) -> Job {
+        Job {
+            max_image_size: id,
+            ..Job::default()
+        }
+    }
+
+    fn queue(ids: &[u32]) -> JobQueue {
+        let mut queue = JobQueue::default();
+        for &id in ids {
+            queue.add(job(id));
+        }
+        queue
+    }
+
+    fn ids(queue: &JobQueue) -> Vec<u32> {
+        queue
+            .jobs
+            .iter()
+            .map(|queued_job| queued_job.job.max_image_size)
+            .collect()
+    }
+
+    fn failed() -> OutputProgress {
+        OutputProgress::Failed {
+            error: JobError::from(OutputError::VulkanError),
+            outputs: vec![],
+        }
+    }
+
+    #[test]
+    fn jobs_can_be_moved_anywhere() {
+        let mut queue = queue(&[1, 2, 3, 4]);
+        queue.move_job(0, 2);
+        assert_eq!(ids(&queue), [2, 3, 1, 4]);
+        queue.move_job(3, 0);
+        assert_eq!(ids(&queue), [4, 2, 3, 1]);
+        // Moving past the end moves the job to the end.
+        queue.move_job(1, 10);
+        assert_eq!(ids(&queue), [4, 3, 1, 2]);
+    }
+
+    #[test]
+    fn jobs_run_in_order_unless_paused() {
+        let mut queue = queue(&[1, 2]);
+        queue.paused = true;
+        assert!(queue.start_next().is_none());
+
+        queue.paused = false;
+        assert_eq!(queue.start_next().unwrap().max_image_size, 1);
+        queue.finish(&failed());
+        assert_eq!(queue.start_next().unwrap().max_image_size, 2);
+        queue.finish(&OutputProgress::Succeeded {
+            output_count: 0,
+            disabled_count: 0,
+            unchanged_count: 0,
+            outputs: vec![],
+        });
+        assert!(queue.start_next().is_none());
+
+        assert!(matches!(queue.jobs[0].state, QueuedJobState::Failed(_)));
+        assert!(matches!(queue.jobs[1].state, QueuedJobState::Done));
+    }
+
+    #[test]
+    fn retried_jobs_run_again() {
+        let mut queue = queue(&[1, 2]);
+        queue.start_next();
+        queue.finish(&failed());
+        queue.retry(0);
+        assert!(matches!(queue.jobs[0].state, QueuedJobState::Waiting));
+        assert_eq!(queue.start_next().unwrap().max_image_size, 1);
+    }
+
+    #[test]
+    fn clearing_removes_only_successful_jobs() {
+        let mut queue = queue(&[1, 2, 3]);
+        queue.jobs[0].state = QueuedJobState::Done;
+        queue.jobs[1].state = QueuedJobState::Failed(JobError::from(OutputError::VulkanError));
+        queue.clear_done();
+        assert_eq!(ids(&queue), [2, 3]);
+    }
+
+    #[test]
+    fn restored_jobs_that_were_running_start_again() {
+        let mut queue = queue(&[1, 2, 3]);
+        queue.paused = true;
+        queue.jobs[0].state = QueuedJobState::Done;
+        queue.jobs[1].state = QueuedJobState::Running;
+        queue.jobs[2].job.version = job_file::JOB_VERSION + 1;
+
+        let queue = JobQueue::restore(&ron::to_string(&queue).unwrap()).unwrap();
+        assert!(queue.paused);
+        assert_eq!(ids(&queue), [1, 2, 3]);
+        assert!(matches!(queue.jobs[0].state, QueuedJobState::Done));
+        assert!(matches!(queue.jobs[1].state, QueuedJobState::Waiting));
+        assert_eq!(queue.jobs[2].job.version, job_file::JOB_VERSION);
+    }
+}
@@ -0,0 +1,27 @@
+// gltf-ibl-sampler-egui/src/radiance.rs
+
+use crate::texture::Texture;
+use anyhow::Error;
+use image::codecs::hdr::HdrEncoder;
+use image::Rgb;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Writes a single mip level to its own Radiance `.hdr` file, with cubemaps laid out in a
+/// horizontal cross.
+///
+/// The format has no alpha channel and can't store negative values, so alpha is dropped and
+/// negative values are clamped to zero. Levels are usually written next to each other, as named
+/// by [`crate::texture::mip_level_path`].
+pub(crate) fn write_level(path: &Path, texture: &Texture, level_index: usize) -> Result<(), Error> {
+    let (width, height, pixels) = texture.level_image(level_index);
+    let pixels: Vec<_> = pixels
+        .chunks_exact(4)
+        .map(|pixel| Rgb([pixel[0].max(0.0), pixel[1].max(0.0), pixel[2].max(0.0)]))
+        .collect();
+
+    let file = File::create(path)?;
+    HdrEncoder::new(BufWriter::new(file)).encode(&pixels, width as usize, height as usize)?;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+// gltf-ibl-sampler-egui/src/reveal.rs
+//
+// Showing generated files in the platform's file manager.
+
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Opens the file manager with the file selected, or at least shows the directory containing it
+/// on platforms that can't select files.
+pub(crate) fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    let mut command = {
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        let mut command = Command::new("explorer");
+        command.arg(select);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    };
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut command = Command::new("xdg-open");
+        command.arg(directory);
+        command
+    };
+
+    // Don't wait for the file manager, which may keep running after the window is closed.
+    command.spawn().map(drop)
+}
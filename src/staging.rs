@@ -0,0 +1,196 @@
+// gltf-ibl-sampler-egui/src/staging.rs
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tempfile::{Builder, TempPath};
+
+/// Files that are written to temporary siblings of their final paths and only moved into place
+/// once all of them have been written.
+///
+/// Dropping a `Staging` without committing it deletes everything that was staged, leaving the
+/// previous versions of the files untouched.
+#[derive(Default)]
+pub(crate) struct Staging {
+    files: Vec<(TempPath, PathBuf)>,
+}
+
+/// The error returned when the staged files couldn't all be moved into place.
+#[derive(Debug)]
+pub(crate) struct CommitError {
+    pub(crate) error: io::Error,
+    /// Files that had already been replaced and couldn't be put back the way they were. If this is
+    /// empty, none of the files were changed.
+    pub(crate) unrestored_paths: Vec<PathBuf>,
+}
+
+impl Staging {
+    pub(crate) fn new() -> Staging {
+        Staging::default()
+    }
+
+    /// Returns a temporary path to write in place of `path`.
+    ///
+    /// The temporary file is hidden in the same directory, so that it can be renamed into place,
+    /// and keeps the extension of `path`, in case the writer looks at it.
+    pub(crate) fn stage(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let temp_path = temp_sibling(path)?;
+        let staged_path = temp_path.to_path_buf();
+        self.files.push((temp_path, path.to_owned()));
+        Ok(staged_path)
+    }
+
+    /// Moves every staged file into place.
+    ///
+    /// If any file can't be moved, the files that were already moved are put back the way they
+    /// were, so either all of the files are replaced or none of them are. Putting files back can
+    /// fail too, though, in which case the error says which files may have been replaced.
+    pub(crate) fn commit(self) -> Result<(), CommitError> {
+        let mut committed = vec![];
+        for (staged_path, path) in self.files {
+            match commit_file(staged_path, &path) {
+                Ok(backup) => committed.push((path, backup)),
+                Err((error, restored)) => {
+                    let mut unrestored_paths = roll_back(committed);
+                    if !restored {
+                        unrestored_paths.push(path);
+                    }
+                    return Err(CommitError {
+                        error,
+                        unrestored_paths,
+                    });
+                }
+            }
+        }
+
+        // Dropping the backups deletes them.
+        Ok(())
+    }
+}
+
+impl Display for CommitError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        self.error.fmt(formatter)
+    }
+}
+
+impl Error for CommitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// Moves a staged file into place, returning the previous version of the file, if there was one.
+// On failure, also returns whether the previous version of the file is still in place.
+fn commit_file(staged_path: TempPath, path: &Path) -> Result<Option<TempPath>, (io::Error, bool)> {
+    let backup = if path.exists() {
+        let backup = temp_sibling(path).map_err(|error| (error, true))?;
+        fs::rename(path, &backup).map_err(|error| (error, true))?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    match staged_path.persist(path) {
+        Ok(()) => Ok(backup),
+        Err(error) => {
+            let restored = match backup {
+                Some(backup) => restore(backup, path),
+                None => true,
+            };
+            Err((error.error, restored))
+        }
+    }
+}
+
+// Puts the files that were moved into place back the way they were, returning the paths of any
+// that couldn't be.
+fn roll_back(committed: Vec<(PathBuf, Option<TempPath>)>) -> Vec<PathBuf> {
+    let mut unrestored_paths = vec![];
+    for (path, backup) in committed.into_iter().rev() {
+        let restored = match backup {
+            Some(backup) => restore(backup, &path),
+            None => fs::remove_file(&path).is_ok(),
+        };
+        if !restored {
+            unrestored_paths.push(path);
+        }
+    }
+    unrestored_paths
+}
+
+// Moves the previous version of a file back into place. If that fails, the previous version is
+// left where it is rather than deleted, so that it can still be recovered by hand.
+fn restore(backup: TempPath, path: &Path) -> bool {
+    match backup.persist(path) {
+        Ok(()) => true,
+        Err(error) => {
+            drop(error.path.keep());
+            false
+        }
+    }
+}
+
+fn temp_sibling(path: &Path) -> io::Result<TempPath> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut prefix = OsString::from(".");
+    prefix.push(path.file_stem().unwrap_or_default());
+    prefix.push(".");
+    let suffix = match path.extension() {
+        Some(extension) => format!(".{}", extension.to_string_lossy()),
+        None => String::new(),
+    };
+
+    Ok(Builder::new()
+        .prefix(&prefix)
+        .suffix(&suffix)
+        .tempfile_in(dir)?
+        .into_temp_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_replaces_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("first.txt"), dir.path().join("second.txt"));
+        fs::write(&first, "old").unwrap();
+
+        let mut staging = Staging::new();
+        fs::write(staging.stage(&first).unwrap(), "new").unwrap();
+        fs::write(staging.stage(&second).unwrap(), "new").unwrap();
+        staging.commit().unwrap();
+
+        assert_eq!(fs::read_to_string(&first).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn failed_commit_puts_files_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.txt");
+        fs::write(&first, "old").unwrap();
+        // A file can't replace a directory, so committing the second file fails.
+        let second = dir.path().join("second");
+        fs::create_dir(&second).unwrap();
+
+        let mut staging = Staging::new();
+        fs::write(staging.stage(&first).unwrap(), "new").unwrap();
+        fs::write(staging.stage(&second).unwrap(), "new").unwrap();
+        let error = staging.commit().unwrap_err();
+
+        assert!(error.unrestored_paths.is_empty());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old");
+        assert!(second.is_dir());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,268 @@
+// gltf-ibl-sampler-egui/src/texture.rs
+
+use crate::astc;
+use crate::generator::TargetFormat;
+use crate::ktx2::{Ktx2Texture, VK_FORMAT_R32G32B32A32_SFLOAT};
+use anyhow::Error;
+use half::f16;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+// The largest value representable in `E5B9G9R9_UFLOAT_PACK32`.
+const MAX_RGB9E5: f32 = 65408.0;
+
+// The position of each cubemap face in a horizontal cross, in units of faces:
+//
+//          +Y
+//      -X  +Z  +X  -Z
+//          -Y
+static CROSS_FACE_POSITIONS: [(usize, usize); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+
+/// A floating-point RGBA texture with a full set of mip levels: either a cubemap, as read back
+/// from the sampler, or a 2D texture reprojected from one.
+pub(crate) struct Texture {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// For each mip level, starting with the largest, the faces of that level. 2D textures have
+    /// one face, and cubemaps have six in +X, -X, +Y, -Y, +Z, -Z order. Each face is stored as
+    /// RGBA values, row by row.
+    pub(crate) levels: Vec<Vec<Vec<f32>>>,
+}
+
+impl Texture {
+    pub(crate) fn from_ktx2(texture: &Ktx2Texture) -> Result<Texture, Error> {
+        if texture.vk_format != VK_FORMAT_R32G32B32A32_SFLOAT || texture.face_count == 0 {
+            return Err(Error::msg("Expected a floating-point texture"));
+        }
+
+        let mut levels = vec![];
+        for (level_index, level) in texture.levels.iter().enumerate() {
+            let (width, height) = texture.level_size(level_index);
+            let face_size = width as usize * height as usize * 16;
+            if level.len() < face_size * texture.face_count as usize {
+                return Err(Error::msg("Texture mip level is truncated"));
+            }
+
+            levels.push(
+                level
+                    .chunks_exact(face_size)
+                    .take(texture.face_count as usize)
+                    .map(|face| {
+                        face.chunks_exact(4)
+                            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                            .collect()
+                    })
+                    .collect(),
+            );
+        }
+
+        Ok(Texture {
+            width: texture.width,
+            height: texture.height,
+            levels,
+        })
+    }
+
+    pub(crate) fn face_count(&self) -> u32 {
+        self.levels.first().map_or(1, |faces| faces.len() as u32)
+    }
+
+    pub(crate) fn is_cubemap(&self) -> bool {
+        self.face_count() == 6
+    }
+
+    /// Returns the width and height of the given mip level.
+    pub(crate) fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Converts every face of every mip level to the given format, one thread per face.
+    pub(crate) fn encode(&self, target_format: TargetFormat) -> Vec<Vec<Vec<u8>>> {
+        self.levels
+            .iter()
+            .enumerate()
+            .map(|(level_index, faces)| {
+                let (width, height) = self.level_size(level_index);
+                thread::scope(|scope| {
+                    let faces: Vec<_> = faces
+                        .iter()
+                        .map(|face| {
+                            scope.spawn(move || encode_pixels(face, width, height, target_format))
+                        })
+                        .collect();
+                    faces.into_iter().map(|face| face.join().unwrap()).collect()
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn to_ktx2(&self, target_format: TargetFormat) -> Ktx2Texture {
+        Ktx2Texture {
+            vk_format: target_format as u32,
+            width: self.width,
+            height: self.height,
+            face_count: self.face_count(),
+            levels: self
+                .encode(target_format)
+                .into_iter()
+                .map(|faces| faces.concat())
+                .collect(),
+        }
+    }
+
+    /// Returns a single image for the given mip level, along with its width and height.
+    ///
+    /// The faces of cubemaps are laid out in a horizontal cross, and texels outside the cross are
+    /// transparent black.
+    pub(crate) fn level_image(&self, level: usize) -> (u32, u32, Cow<'_, [f32]>) {
+        let (width, height) = self.level_size(level);
+        let faces = &self.levels[level];
+        if !self.is_cubemap() {
+            return (width, height, Cow::Borrowed(&faces[0]));
+        }
+
+        let size = width as usize;
+        let mut pixels = vec![0.0; size * 4 * size * 3 * 4];
+        for (face, &(face_x, face_y)) in faces.iter().zip(CROSS_FACE_POSITIONS.iter()) {
+            for (row_index, row) in face.chunks_exact(size * 4).enumerate() {
+                let start = ((face_y * size + row_index) * size * 4 + face_x * size) * 4;
+                pixels[start..(start + size * 4)].copy_from_slice(row);
+            }
+        }
+        (width * 4, height * 3, Cow::Owned(pixels))
+    }
+}
+
+/// Converts an RGBA floating-point image to the given format.
+pub(crate) fn encode_pixels(
+    pixels: &[f32],
+    width: u32,
+    height: u32,
+    target_format: TargetFormat,
+) -> Vec<u8> {
+    match target_format {
+        TargetFormat::R8G8B8A8Unorm => pixels
+            .iter()
+            .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect(),
+        TargetFormat::R9G9B9E5Ufloat => pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| pack_rgb9e5([pixel[0], pixel[1], pixel[2]]).to_le_bytes())
+            .collect(),
+        TargetFormat::R16G16B16A16Sfloat => pixels
+            .iter()
+            .flat_map(|value| f16::from_f32(*value).to_le_bytes())
+            .collect(),
+        TargetFormat::R32G32B32A32Sfloat => pixels
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect(),
+        TargetFormat::Astc4x4Sfloat | TargetFormat::Astc6x6Sfloat => {
+            let block_size = target_format.astc_block_size().unwrap();
+            astc::encode_hdr(pixels, width, height, block_size, block_size)
+        }
+    }
+}
+
+/// Returns the path that the given mip level is written to when each level gets its own file.
+pub(crate) fn mip_level_path(path: &Path, level: usize) -> PathBuf {
+    if level == 0 {
+        return path.to_owned();
+    }
+
+    let mut file_name = path.file_stem().unwrap_or_default().to_owned();
+    file_name.push(format!("_mip{}", level));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+// Packs a color into the shared exponent format, as described in the Vulkan specification.
+fn pack_rgb9e5(color: [f32; 3]) -> u32 {
+    // This also flushes NaNs to zero.
+    let color = color.map(|value| {
+        if value > 0.0 {
+            value.min(MAX_RGB9E5)
+        } else {
+            0.0
+        }
+    });
+    let max = color[0].max(color[1]).max(color[2]);
+
+    let mut exponent = (max.log2().floor() as i32).max(-16) + 1 + 15;
+    let mut scale = f32::powi(2.0, exponent - 15 - 9);
+    if (max / scale).round() as u32 == 512 {
+        exponent += 1;
+        scale *= 2.0;
+    }
+
+    let [red, green, blue] = color.map(|value| (value / scale).round() as u32);
+    red | green << 9 | blue << 18 | (exponent as u32) << 27
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unpacks a color from the shared exponent format, as described in the Vulkan specification.
+    fn unpack_rgb9e5(packed: u32) -> [f32; 3] {
+        let scale = f32::powi(2.0, (packed >> 27) as i32 - 15 - 9);
+        [0, 9, 18].map(|shift| ((packed >> shift) & 0x1ff) as f32 * scale)
+    }
+
+    #[test]
+    fn rgb9e5_packs_exact_values() {
+        assert_eq!(pack_rgb9e5([0.0; 3]), 0);
+        assert_eq!(pack_rgb9e5([1.0, 0.0, 0.0]), 256 | 16 << 27);
+        assert_eq!(
+            pack_rgb9e5([0.0, 0.5, 0.25]),
+            256 << 9 | 128 << 18 | 15 << 27
+        );
+        assert_eq!(
+            pack_rgb9e5([MAX_RGB9E5; 3]),
+            0x1ff | 0x1ff << 9 | 0x1ff << 18 | 31 << 27
+        );
+    }
+
+    #[test]
+    fn rgb9e5_rounding_up_to_the_next_exponent() {
+        // 1.999 rounds to a mantissa of 512 with an exponent of 0, which doesn't fit.
+        assert_eq!(
+            unpack_rgb9e5(pack_rgb9e5([1.999, 0.0, 0.0])),
+            [2.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn rgb9e5_clamps_values_it_cannot_represent() {
+        assert_eq!(pack_rgb9e5([-1.0, f32::NAN, 0.0]), 0);
+        assert_eq!(
+            unpack_rgb9e5(pack_rgb9e5([f32::INFINITY, 1.0e9, -0.0])),
+            [MAX_RGB9E5, MAX_RGB9E5, 0.0]
+        );
+    }
+
+    #[test]
+    fn rgb9e5_round_trips_within_its_precision() {
+        for color in [
+            [0.1f32, 0.2, 0.3],
+            [1000.0, 1.0, 0.001],
+            [3.0e-5, 2.0e-5, 1.0e-5],
+            [12.5, 12.5, 12.5],
+        ] {
+            let max = color[0].max(color[1]).max(color[2]);
+            let unpacked = unpack_rgb9e5(pack_rgb9e5(color));
+            for (value, unpacked_value) in color.iter().zip(unpacked) {
+                assert!(
+                    (value - unpacked_value).abs() <= max / 512.0,
+                    "{:?} became {:?}",
+                    color,
+                    unpacked
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,490 @@
+// gltf-ibl-sampler-egui/src/validation.rs
+
+use crate::generator::{ContainerFormat, GltfExportMode, Job, OutputError, OutputPath, Projection};
+use crate::{os_path, texture, ToLocalizedString};
+use rust_i18n::t;
+use std::collections::HashMap;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+/// A setting of a job that can have a problem.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum JobField {
+    InputPath,
+    MaxImageSize,
+    Output(usize, OutputField),
+    GltfExport,
+    GltfSnippetPath,
+    GltfPath,
+    BevyBundle,
+    BevySnippetPath,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum OutputField {
+    CubemapPath,
+    LutPath,
+    TargetFormat,
+    MipLevelCount,
+    CubemapResolution,
+    OctahedralResolution,
+    SampleCount,
+}
+
+/// A problem with a job that would make generation fail.
+pub(crate) struct ValidationIssue {
+    pub(crate) field: JobField,
+    pub(crate) kind: ValidationIssueKind,
+}
+
+pub(crate) enum ValidationIssueKind {
+    NoInputPath,
+    InputNotFound,
+    NoOutputPath,
+    DirectoryNotFound,
+    DuplicatePath,
+    Zero,
+    TooManyMipLevels { max: u32 },
+    TooManyMipLevelsForMaxImageSize { max: u32 },
+    TooManyMipLevelsForOctahedralResolution { max: u32 },
+    PathNotUnicode,
+    AstcNotSupportedInDds,
+    Export(OutputError),
+}
+
+impl Job {
+    /// Checks the job for problems that would make generation fail, without doing any work.
+    ///
+    /// Output directories only have to exist for paths that the user chose; automatically-chosen
+    /// paths can be in subdirectories, which are created during generation.
+    pub(crate) fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = vec![];
+        let mut issue = |field, kind| issues.push(ValidationIssue { field, kind });
+
+        if self.input_path.as_os_str().is_empty() {
+            issue(JobField::InputPath, ValidationIssueKind::NoInputPath);
+        } else if !self.input_path.is_file() {
+            issue(JobField::InputPath, ValidationIssueKind::InputNotFound);
+        }
+        if self.max_image_size == 0 {
+            issue(JobField::MaxImageSize, ValidationIssueKind::Zero);
+        }
+
+        // Gather every path that gets written, so that duplicates can be found. Outputs that write
+        // each mip level to its own file also write the files in `level_paths`.
+        let mut paths: Vec<(JobField, &OutputPath)> = vec![];
+        let mut level_paths: Vec<(JobField, PathBuf)> = vec![];
+
+        for (index, output) in self.outputs.iter().enumerate() {
+            // Disabled outputs aren't written, so they can't fail.
+            if !output.enabled {
+                continue;
+            }
+
+            let field = |output_field| JobField::Output(index, output_field);
+            paths.push((field(OutputField::CubemapPath), &output.out_cubemap));
+
+            if output.container_format == ContainerFormat::Dds
+                && output.target_format.astc_block_size().is_some()
+            {
+                issue(
+                    field(OutputField::TargetFormat),
+                    ValidationIssueKind::AstcNotSupportedInDds,
+                );
+            }
+
+            if output.cubemap_resolution == Some(0) {
+                issue(
+                    field(OutputField::CubemapResolution),
+                    ValidationIssueKind::Zero,
+                );
+            }
+
+            // An automatic resolution is never larger than the input, which is scaled to fit
+            // within the maximum image size, so that limits the number of mip levels too.
+            let max_mip_level_count = output
+                .cubemap_resolution
+                .unwrap_or(self.max_image_size)
+                .checked_ilog2()
+                .map(|log2| log2 + 1);
+            // Octahedral maps stop at 1×1, so their resolution can limit them further.
+            let max_octahedral_level_count = match output.projection {
+                Projection::Octahedral => output
+                    .octahedral_settings
+                    .resolution
+                    .and_then(u32::checked_ilog2)
+                    .map(|log2| log2 + 1),
+                _ => None,
+            };
+            match (
+                output.mip_level_count,
+                max_mip_level_count,
+                max_octahedral_level_count,
+            ) {
+                (Some(0), _, _) => {
+                    issue(field(OutputField::MipLevelCount), ValidationIssueKind::Zero)
+                }
+                (Some(mip_level_count), Some(max), _) if mip_level_count > max => issue(
+                    field(OutputField::MipLevelCount),
+                    match output.cubemap_resolution {
+                        Some(_) => ValidationIssueKind::TooManyMipLevels { max },
+                        None => ValidationIssueKind::TooManyMipLevelsForMaxImageSize { max },
+                    },
+                ),
+                (Some(mip_level_count), _, Some(max)) if mip_level_count > max => issue(
+                    field(OutputField::MipLevelCount),
+                    ValidationIssueKind::TooManyMipLevelsForOctahedralResolution { max },
+                ),
+                _ => {}
+            }
+
+            let cubemap_path = &output.out_cubemap.path;
+            if output.container_format.has_file_per_level() && !cubemap_path.as_os_str().is_empty()
+            {
+                let level_count = output
+                    .mip_level_count
+                    .or(max_mip_level_count)
+                    .unwrap_or_default()
+                    .min(max_octahedral_level_count.unwrap_or(u32::MAX));
+                level_paths.extend((1..level_count as usize).map(|level| {
+                    (
+                        field(OutputField::CubemapPath),
+                        texture::mip_level_path(cubemap_path, level),
+                    )
+                }));
+            }
+
+            if output.octahedral_settings.resolution == Some(0) {
+                issue(
+                    field(OutputField::OctahedralResolution),
+                    ValidationIssueKind::Zero,
+                );
+            }
+
+            if let Some(ref filter_settings) = output.filter_settings {
+                paths.push((field(OutputField::LutPath), &filter_settings.out_lut));
+                if filter_settings.sample_count == 0 {
+                    issue(field(OutputField::SampleCount), ValidationIssueKind::Zero);
+                }
+            }
+
+            // The sampler writes these files itself.
+            let lut_path = output
+                .filter_settings
+                .as_ref()
+                .map(|filter_settings| (OutputField::LutPath, &filter_settings.out_lut.path));
+            for (output_field, path) in
+                iter::once((OutputField::CubemapPath, &output.out_cubemap.path)).chain(lut_path)
+            {
+                if !os_path::sampler_can_open(path) {
+                    issue(field(output_field), ValidationIssueKind::PathNotUnicode);
+                }
+            }
+        }
+
+        match self.gltf_export.mode {
+            GltfExportMode::Disabled => {}
+            GltfExportMode::Snippet => {
+                paths.push((JobField::GltfSnippetPath, &self.gltf_export.snippet_path));
+            }
+            GltfExportMode::Inject => {
+                if !self.gltf_export.gltf_path.is_file() {
+                    issue(JobField::GltfPath, ValidationIssueKind::InputNotFound);
+                }
+            }
+        }
+        if let Err(error) = self.gltf_export.source_outputs(&self.outputs) {
+            issue(JobField::GltfExport, ValidationIssueKind::Export(error));
+        }
+
+        if self.bevy_bundle.version.is_some() {
+            paths.push((JobField::BevySnippetPath, &self.bevy_bundle.snippet_path));
+        }
+        if let Err(error) = self.bevy_bundle.source_outputs(&self.outputs) {
+            issue(JobField::BevyBundle, ValidationIssueKind::Export(error));
+        }
+
+        let mut path_counts: HashMap<&Path, usize> = HashMap::new();
+        let all_paths = paths
+            .iter()
+            .map(|(_, output_path)| &*output_path.path)
+            .chain(level_paths.iter().map(|(_, path)| &**path));
+        for path in all_paths {
+            *path_counts.entry(path).or_default() += 1;
+        }
+        let mut duplicate_fields = vec![];
+        for (field, output_path) in &paths {
+            let path = &output_path.path;
+            if path.as_os_str().is_empty() {
+                issue(*field, ValidationIssueKind::NoOutputPath);
+            } else if path_counts[&**path] > 1 {
+                duplicate_fields.push(*field);
+            } else if !output_path.automatic_filename && !parent_exists(path) {
+                issue(*field, ValidationIssueKind::DirectoryNotFound);
+            }
+        }
+        for (field, path) in &level_paths {
+            if path_counts[&**path] > 1 && !duplicate_fields.contains(field) {
+                duplicate_fields.push(*field);
+            }
+        }
+        for field in duplicate_fields {
+            issue(field, ValidationIssueKind::DuplicatePath);
+        }
+
+        issues
+    }
+}
+
+fn parent_exists(path: &Path) -> bool {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.is_dir(),
+        _ => true,
+    }
+}
+
+/// Returns the issues with the given field.
+pub(crate) fn field_issues(
+    issues: &[ValidationIssue],
+    field: JobField,
+) -> impl Iterator<Item = &ValidationIssue> {
+    issues.iter().filter(move |issue| issue.field == field)
+}
+
+impl ToLocalizedString for ValidationIssueKind {
+    fn to_localized_string(&self) -> String {
+        match *self {
+            ValidationIssueKind::NoInputPath => t!("validation.no.input.path"),
+            ValidationIssueKind::InputNotFound => t!("validation.input.not.found"),
+            ValidationIssueKind::NoOutputPath => t!("validation.no.output.path"),
+            ValidationIssueKind::DirectoryNotFound => t!("validation.directory.not.found"),
+            ValidationIssueKind::DuplicatePath => t!("validation.duplicate.path"),
+            ValidationIssueKind::Zero => t!("validation.zero"),
+            ValidationIssueKind::TooManyMipLevels { max } => {
+                t!("validation.too.many.mip.levels", max = max)
+            }
+            ValidationIssueKind::TooManyMipLevelsForMaxImageSize { max } => {
+                t!(
+                    "validation.too.many.mip.levels.for.max.image.size",
+                    max = max
+                )
+            }
+            ValidationIssueKind::TooManyMipLevelsForOctahedralResolution { max } => {
+                t!(
+                    "validation.too.many.mip.levels.for.octahedral.resolution",
+                    max = max
+                )
+            }
+            ValidationIssueKind::PathNotUnicode => t!("validation.path.not.unicode"),
+            ValidationIssueKind::AstcNotSupportedInDds => {
+                t!("output.error.astc.not.supported.in.dds")
+            }
+            ValidationIssueKind::Export(ref error) => error.to_localized_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{Output, TargetFormat};
+    use std::fs;
+    use tempfile::TempDir;
+
+    // Returns a job with an input and one output in a new directory, which has no issues.
+    fn valid_job() -> (TempDir, Job) {
+        let dir = tempfile::tempdir().unwrap();
+        let input_path = dir.path().join("studio.hdr");
+        fs::write(&input_path, "").unwrap();
+        let job = Job {
+            input_path,
+            outputs: vec![output(dir.path().join("studio.ktx2"))],
+            ..Job::default()
+        };
+        (dir, job)
+    }
+
+    fn output(path: PathBuf) -> Output {
+        Output {
+            out_cubemap: OutputPath {
+                path,
+                automatic_filename: false,
+            },
+            ..Output::default()
+        }
+    }
+
+    #[test]
+    fn valid_jobs_have_no_issues() {
+        let (_dir, job) = valid_job();
+        assert!(job.validate().is_empty());
+    }
+
+    #[test]
+    fn input_must_exist() {
+        let (dir, mut job) = valid_job();
+        job.input_path = PathBuf::new();
+        let issues = job.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0].kind, ValidationIssueKind::NoInputPath));
+
+        job.input_path = dir.path().join("missing.hdr");
+        let issues = job.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0].kind, ValidationIssueKind::InputNotFound));
+    }
+
+    #[test]
+    fn disabled_outputs_are_not_checked() {
+        let (_dir, mut job) = valid_job();
+        job.outputs.push(Output {
+            enabled: false,
+            cubemap_resolution: Some(0),
+            ..output(PathBuf::new())
+        });
+        assert!(job.validate().is_empty());
+    }
+
+    #[test]
+    fn only_chosen_directories_must_exist() {
+        let (dir, mut job) = valid_job();
+        job.outputs[0].out_cubemap.path = dir.path().join("missing").join("studio.ktx2");
+        let issues = job.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(
+            issues[0].kind,
+            ValidationIssueKind::DirectoryNotFound
+        ));
+
+        job.outputs[0].out_cubemap.automatic_filename = true;
+        assert!(job.validate().is_empty());
+    }
+
+    #[test]
+    fn duplicate_paths_are_reported_for_every_output() {
+        let (_dir, mut job) = valid_job();
+        job.outputs.push(job.outputs[0].clone());
+        let issues = job.validate();
+        assert_eq!(issues.len(), 2);
+        for (index, issue) in issues.iter().enumerate() {
+            assert!(issue.field == JobField::Output(index, OutputField::CubemapPath));
+            assert!(matches!(issue.kind, ValidationIssueKind::DuplicatePath));
+        }
+    }
+
+    #[test]
+    fn mip_level_files_count_as_duplicates() {
+        let (dir, mut job) = valid_job();
+        job.outputs = vec![
+            Output {
+                container_format: ContainerFormat::HdrPerLevel,
+                mip_level_count: Some(3),
+                ..output(dir.path().join("sky.hdr"))
+            },
+            output(dir.path().join("sky_mip2.hdr")),
+        ];
+        let issues = job.validate();
+        assert_eq!(issues.len(), 2);
+        for index in 0..2 {
+            assert!(issues.iter().any(|issue| {
+                issue.field == JobField::Output(index, OutputField::CubemapPath)
+                    && matches!(issue.kind, ValidationIssueKind::DuplicatePath)
+            }));
+        }
+
+        // The first output only writes levels 0 and 1 now.
+        job.outputs[0].mip_level_count = Some(2);
+        assert!(job.validate().is_empty());
+    }
+
+    #[test]
+    fn mip_level_count_is_limited_by_the_resolution() {
+        let (_dir, mut job) = valid_job();
+        job.outputs[0].cubemap_resolution = Some(256);
+        job.outputs[0].mip_level_count = Some(9);
+        assert!(job.validate().is_empty());
+
+        job.outputs[0].mip_level_count = Some(10);
+        let issues = job.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(
+            issues[0].kind,
+            ValidationIssueKind::TooManyMipLevels { max: 9 }
+        ));
+    }
+
+    #[test]
+    fn automatic_resolutions_are_limited_by_the_max_image_size() {
+        let (_dir, mut job) = valid_job();
+        job.max_image_size = 1024;
+        job.outputs[0].mip_level_count = Some(12);
+        let issues = job.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(
+            issues[0].kind,
+            ValidationIssueKind::TooManyMipLevelsForMaxImageSize { max: 11 }
+        ));
+    }
+
+    #[test]
+    fn octahedral_mip_level_count_is_limited_by_the_octahedral_resolution() {
+        let (_dir, mut job) = valid_job();
+        job.outputs[0].projection = Projection::Octahedral;
+        job.outputs[0].cubemap_resolution = Some(256);
+        job.outputs[0].octahedral_settings.resolution = Some(64);
+        job.outputs[0].mip_level_count = Some(7);
+        assert!(job.validate().is_empty());
+
+        job.outputs[0].mip_level_count = Some(8);
+        let issues = job.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].field == JobField::Output(0, OutputField::MipLevelCount));
+        assert!(matches!(
+            issues[0].kind,
+            ValidationIssueKind::TooManyMipLevelsForOctahedralResolution { max: 7 }
+        ));
+
+        // The default octahedral resolution is twice the cubemap's, so the cubemap limits it.
+        job.outputs[0].octahedral_settings.resolution = None;
+        job.outputs[0].mip_level_count = Some(9);
+        assert!(job.validate().is_empty());
+    }
+
+    #[test]
+    fn zeros_are_reported() {
+        let (_dir, mut job) = valid_job();
+        job.max_image_size = 0;
+        job.outputs[0].cubemap_resolution = Some(0);
+        job.outputs[0].mip_level_count = Some(0);
+        let fields: Vec<_> = job
+            .validate()
+            .into_iter()
+            .filter(|issue| matches!(issue.kind, ValidationIssueKind::Zero))
+            .map(|issue| issue.field)
+            .collect();
+        assert!(
+            fields
+                == [
+                    JobField::MaxImageSize,
+                    JobField::Output(0, OutputField::CubemapResolution),
+                    JobField::Output(0, OutputField::MipLevelCount),
+                ]
+        );
+    }
+
+    #[test]
+    fn dds_cannot_hold_astc() {
+        let (dir, mut job) = valid_job();
+        job.outputs[0] = Output {
+            container_format: ContainerFormat::Dds,
+            target_format: TargetFormat::Astc4x4Sfloat,
+            ..output(dir.path().join("studio.dds"))
+        };
+        let issues = job.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(
+            issues[0].kind,
+            ValidationIssueKind::AstcNotSupportedInDds
+        ));
+    }
+}
@@ -0,0 +1,199 @@
+// gltf-ibl-sampler-egui/src/worker.rs
+
+use crate::capture::{self, RecentLines};
+use crate::generator::{self, Job, JobError, OutputError, OutputProgress, OutputStatus};
+use log::{error, info, warn, LevelFilter, Log, Metadata, Record};
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Passing this argument to the app's executable starts a worker process instead of the UI.
+pub(crate) static WORKER_ARGUMENT: &str = "--sampler-worker";
+
+static STDERR_LOGGER: StderrLogger = StderrLogger;
+
+/// A handle to the worker process running a job, if there is one.
+#[derive(Clone, Default)]
+pub(crate) struct WorkerHandle {
+    child: Arc<Mutex<Option<Child>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+// Worker processes log to `stderr`, which the app forwards to its own log.
+struct StderrLogger;
+
+impl WorkerHandle {
+    pub(crate) fn is_running(&self) -> bool {
+        self.child.lock().unwrap().is_some()
+    }
+
+    /// Kills the worker process. The job then fails with [`OutputError::WorkerStopped`].
+    pub(crate) fn stop(&self) {
+        if let Some(ref mut child) = *self.child.lock().unwrap() {
+            self.stopped.store(true, Ordering::SeqCst);
+            drop(child.kill());
+        }
+    }
+}
+
+/// Runs the job in a new worker process and passes its progress to `report`.
+///
+/// If the worker exits before it finishes the job, the output that it was working on fails with
+/// [`OutputError::WorkerCrashed`].
+pub(crate) fn run(job: &Job, handle: &WorkerHandle, report: &mut dyn FnMut(OutputProgress)) {
+    let child = env::current_exe().and_then(|executable| {
+        Command::new(executable)
+            .arg(WORKER_ARGUMENT)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    });
+    let mut child = match child {
+        Ok(child) => child,
+        Err(error) => {
+            let error = OutputError::FailedToStartWorker(error.to_string());
+            report(OutputProgress::Failed {
+                error: JobError::from(error),
+                outputs: vec![],
+            });
+            return;
+        }
+    };
+    let (mut stdin, stdout, stderr) = (
+        child.stdin.take().unwrap(),
+        child.stdout.take().unwrap(),
+        child.stderr.take().unwrap(),
+    );
+    *handle.child.lock().unwrap() = Some(child);
+
+    // If the worker dies before reading the job, we find out when it exits below.
+    if let Ok(encoded_job) = ron::to_string(job) {
+        drop(stdin.write_all(encoded_job.as_bytes()));
+    }
+    drop(stdin);
+
+    // Log everything that the worker prints, and remember the last few lines.
+    let output_thread = thread::spawn(move || {
+        let mut reader = BufReader::new(stderr);
+        let mut lines = RecentLines::default();
+        let mut line = vec![];
+        while reader
+            .read_until(b'\n', &mut line)
+            .is_ok_and(|count| count > 0)
+        {
+            let text = String::from_utf8_lossy(&line).trim_end().to_owned();
+            info!("{}", text);
+            lines.push(text);
+            line.clear();
+        }
+        lines.to_vec()
+    });
+
+    // Read progress updates until the worker exits, remembering the last status of each output.
+    let mut outputs_finished = 0;
+    let mut outputs = vec![];
+    let mut finished = false;
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match ron::from_str::<OutputProgress>(&line) {
+            Ok(progress) => {
+                if let OutputProgress::InProgress {
+                    outputs_finished: progress_outputs_finished,
+                    ..
+                } = progress
+                {
+                    outputs_finished = progress_outputs_finished;
+                }
+                outputs = progress.outputs().to_vec();
+                finished = progress.is_finished();
+                report(progress);
+            }
+            Err(error) => warn!("Unexpected message from the worker process: {}", error),
+        }
+    }
+
+    let log_tail = output_thread.join().unwrap_or_default();
+    let status = handle
+        .child
+        .lock()
+        .unwrap()
+        .take()
+        .map(|mut child| child.wait());
+    if finished {
+        return;
+    }
+
+    let error = if handle.stopped.load(Ordering::SeqCst) {
+        OutputError::WorkerStopped
+    } else {
+        let status = match status {
+            Some(Ok(status)) => status.to_string(),
+            Some(Err(error)) => error.to_string(),
+            None => String::new(),
+        };
+        OutputError::WorkerCrashed(status)
+    };
+    OutputStatus::fail_running(&mut outputs);
+    report(OutputProgress::Failed {
+        error: JobError {
+            output_index: (outputs_finished < job.outputs.len()).then_some(outputs_finished),
+            log_tail,
+            ..JobError::from(error)
+        },
+        outputs,
+    });
+}
+
+/// The entry point of worker processes: reads a job from `stdin`, runs it, and writes each change
+/// in progress to `stdout` on its own line.
+pub(crate) fn worker_main() {
+    drop(log::set_logger(&STDERR_LOGGER));
+    log::set_max_level(LevelFilter::Info);
+
+    let mut messages = match capture::divert_stdout() {
+        Ok(messages) => messages,
+        Err(error) => {
+            error!("Failed to open the message channel: {}", error);
+            process::exit(1);
+        }
+    };
+
+    let mut encoded_job = String::new();
+    let job: Job = match io::stdin()
+        .read_to_string(&mut encoded_job)
+        .map_err(|error| error.to_string())
+        .and_then(|_| ron::from_str(&encoded_job).map_err(|error| error.to_string()))
+    {
+        Ok(job) => job,
+        Err(error) => {
+            error!("Failed to read the job: {}", error);
+            process::exit(1);
+        }
+    };
+
+    generator::run_job(&job, &mut |progress| {
+        if let Ok(encoded_progress) = ron::to_string(&progress) {
+            drop(writeln!(messages, "{}", encoded_progress));
+        }
+    });
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LevelFilter::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}